@@ -0,0 +1,101 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use payload::{
+    core::OPAQUE_GROUP,
+    fov::{cast_view_cone, cast_view_cone_fixed_step, FieldOfView},
+};
+
+/// Build a headless world with a scattering of opaque colliders for viewers to look at
+fn setup_world() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        TransformPlugin,
+        HierarchyPlugin,
+        RapierPhysicsPlugin::<()>::pixels_per_meter(32.0),
+    ));
+
+    for x in -8..8 {
+        for y in -8..8 {
+            if (x + y) % 3 != 0 {
+                continue;
+            }
+            app.world.spawn((
+                TransformBundle::from_transform(Transform::from_xyz(
+                    x as f32 * 64.0,
+                    y as f32 * 64.0,
+                    0.0,
+                )),
+                Collider::cuboid(12.0, 12.0),
+                CollisionGroups::new(OPAQUE_GROUP, Group::all()),
+            ));
+        }
+    }
+
+    // Let Rapier pick up all our colliders
+    app.update();
+    app.update();
+
+    app
+}
+
+/// Spread `count` viewers around the world, each looking a different way
+fn viewers(app: &mut App, count: usize) -> Vec<(Entity, Vec2, Vec2)> {
+    (0..count)
+        .map(|i| {
+            let angle = i as f32 / count as f32 * TAU;
+            let position = Vec2::from_angle(angle) * 200.0 + Vec2::splat(32.0);
+            let direction = Vec2::from_angle(angle * 3.0);
+
+            (app.world.spawn_empty().id(), position, direction)
+        })
+        .collect()
+}
+
+fn bench_view_cones(c: &mut Criterion) {
+    let mut app = setup_world();
+    let fov = FieldOfView::new(256.0, TAU / 12.0);
+
+    let mut group = c.benchmark_group("view_cone");
+    for count in [1, 10, 100] {
+        let viewers = viewers(&mut app, count);
+        let rapier_context = app.world.resource::<RapierContext>();
+
+        group.bench_with_input(
+            BenchmarkId::new("fixed_step", count),
+            &viewers,
+            |b, viewers| {
+                b.iter(|| {
+                    for &(viewer, origin, direction) in viewers.iter() {
+                        cast_view_cone_fixed_step(
+                            viewer,
+                            origin,
+                            &fov,
+                            direction,
+                            rapier_context,
+                            &(),
+                        );
+                    }
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("corners", count),
+            &viewers,
+            |b, viewers| {
+                b.iter(|| {
+                    for &(viewer, origin, direction) in viewers.iter() {
+                        cast_view_cone(viewer, origin, &fov, direction, rapier_context, &());
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_view_cones);
+criterion_main!(benches);
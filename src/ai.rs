@@ -1,9 +1,102 @@
+use std::f32::consts::TAU;
+
 use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{FireMode, FireWeapon, Weapon},
+    core::PLAYER_GROUP,
+    fov::{FieldOfView, RayResolution, Viewable, VisibleEntities, VisionFaction},
+    health::{Corpse, Damage, DamageKind, Health, OnDeath, Resistances},
+    hearing::Hearing,
+    lighting::LightSource,
+    locomotion::{Locomotion, MoveIntent},
+    player::Player,
+    sprites::Sprites,
+};
+
+/// How close to facing the player a drone must be before it opens fire, in radians
+const AIM_TOLERANCE: f32 = 0.1;
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
 pub struct DroneAI;
 
-pub fn drone_idle(mut drone_qry: Query<&mut Transform, With<DroneAI>>, time: Res<Time>) {
+/// The different kinds of drone, and what they do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Serialize, Deserialize)]
+pub enum DroneKind {
+    /// Shares its field of view with the player
+    Companion,
+    /// Sees for itself, and shoots the player on sight
+    Hostile,
+}
+
+/// The weapon every hostile drone is built with
+pub fn hostile_weapon() -> Weapon {
+    Weapon::new(
+        FireMode::Projectile {
+            speed: 240.0,
+            lifetime: 2.0,
+        },
+        Damage::new(8.0, DamageKind::Kinetic),
+        1.0,
+    )
+    .with_unlimited_ammo()
+}
+
+/// Spawn a drone of the given kind
+pub fn spawn_drone(
+    commands: &mut Commands,
+    sprites: &Sprites,
+    kind: DroneKind,
+    transform: Transform,
+) -> Entity {
+    let mut drone = commands.spawn((
+        SpriteBundle {
+            texture: sprites.drone.clone(),
+            transform,
+            ..Default::default()
+        },
+        CollisionGroups::new(PLAYER_GROUP, Group::all()),
+        FieldOfView::new(128.0, TAU / 10.0).with_resolution(RayResolution::ArcLength(4.0)),
+        kind,
+        DroneAI,
+    ));
+
+    match kind {
+        DroneKind::Companion => {
+            drone.insert(VisionFaction::PLAYER);
+        }
+        DroneKind::Hostile => {
+            // Its FoV is its own; we only see it while it's within our own FoV
+            drone.insert((
+                Collider::ball(12.0),
+                VisionFaction(1),
+                LightSource::cone(128.0, TAU / 10.0, 0.8),
+                Hearing::default(),
+                Health::new(40.0),
+                Resistances {
+                    energy: 0.5,
+                    radiation: 1.0,
+                    vacuum: 1.0,
+                    ..Default::default()
+                },
+                OnDeath::LeaveCorpse,
+                Locomotion::new(96.0, 48.0).with_turn_rate(TAU / 2.0),
+                MoveIntent::default(),
+                hostile_weapon(),
+                Viewable::Dynamic,
+            ));
+        }
+    }
+
+    drone.id()
+}
+
+pub fn drone_idle(
+    mut drone_qry: Query<&mut Transform, (With<DroneAI>, Without<Corpse>)>,
+    time: Res<Time>,
+) {
     for mut drone_transform in drone_qry.iter_mut() {
         let scale = (time.elapsed_seconds() * 4.0).sin() / 20.0 + 1.0;
         drone_transform.scale = Vec3::new(scale, scale, 1.0);
@@ -15,3 +108,35 @@ pub fn drone_idle(mut drone_qry: Query<&mut Transform, With<DroneAI>>, time: Res
         drone_transform.rotate_z((time.elapsed_seconds() * 4.5 - 6.0).sin() / 600.0);
     }
 }
+
+/// Drones that are still alive and armed
+type ArmedDrones = (With<DroneAI>, With<Weapon>, Without<Corpse>);
+
+/// Turn to face, and shoot at, the player whenever an armed drone can see them
+pub fn drone_attack(
+    player_qry: Query<(Entity, &GlobalTransform), With<Player>>,
+    mut drone_qry: Query<
+        (Entity, &GlobalTransform, &VisibleEntities, &mut MoveIntent),
+        ArmedDrones,
+    >,
+    mut shots: EventWriter<FireWeapon>,
+) {
+    let Ok((player, player_transform)) = player_qry.get_single() else {
+        return;
+    };
+
+    for (drone, drone_transform, visible, mut intent) in drone_qry.iter_mut() {
+        if !visible.contains(player) {
+            intent.facing = None;
+            continue;
+        }
+
+        let to_player = (player_transform.translation() - drone_transform.translation()).truncate();
+        intent.facing = Some(to_player);
+
+        let facing = drone_transform.right().truncate();
+        if facing.angle_between(to_player).abs() <= AIM_TOLERANCE {
+            shots.send(FireWeapon { shooter: drone });
+        }
+    }
+}
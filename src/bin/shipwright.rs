@@ -1,5 +1,3 @@
-use std::cmp::{max, min};
-
 use bevy::{
     input::mouse::{MouseMotion, MouseWheel},
     prelude::*,
@@ -207,20 +205,9 @@ fn gather_ship_stats(
         }
     }
 
-    let (min_x, min_y, max_x, max_y) = rooms
-        .iter()
-        .map(|room| (room.min.x, room.min.y, room.max.x, room.max.y))
-        .reduce(|acc, room| {
-            (
-                min(acc.0, room.0),
-                min(acc.1, room.1),
-                max(acc.2, room.2),
-                max(acc.3, room.3),
-            )
-        })
-        .unwrap();
-    let ship_length = max_x - min_x;
-    let ship_width = max_y - min_y;
+    let bounds = rooms.bounds();
+    let ship_length = bounds.width();
+    let ship_width = bounds.height();
 
     let stats = ShipStatistics {
         parameters: *parameters,
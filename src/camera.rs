@@ -1,5 +1,7 @@
 use bevy::prelude::*;
 
+use crate::core::Persistent;
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
 pub struct MainCamera;
 
@@ -7,7 +9,7 @@ pub struct MainCamera;
 pub struct Follow(pub Entity);
 
 pub fn spawn_camera(mut commands: Commands) {
-    commands.spawn((Camera2dBundle::default(), MainCamera));
+    commands.spawn((Camera2dBundle::default(), MainCamera, Persistent));
 }
 
 /// Update the camera position to follow an entity
@@ -0,0 +1,366 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, render::view::RenderLayers, utils::HashSet};
+use bevy_rapier2d::{prelude::*, rapier::geometry::CollisionEventFlags};
+
+use crate::{
+    core::{OPAQUE_GROUP, PLAYER_GROUP, PROJECTILE_GROUP, TRANSLUCENT_GROUP},
+    health::{Damage, TakeDamage},
+    hearing::{Hearing, Noise},
+    input::{Action, ActionState},
+    player::Player,
+};
+
+/// How far in front of the shooter shots start, so they don't hit the shooter
+const MUZZLE_OFFSET: f32 = 18.0;
+
+/// How long muzzle flashes and tracers linger, in seconds
+const EFFECT_TIME: f32 = 0.1;
+
+/// Shot effects sit above the fog of war and ghosts, so heard shots can be seen in the dark
+const EFFECT_Z: f32 = 160.0;
+
+/// How a weapon's shots reach their target
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FireMode {
+    /// Shots hit instantly, e.g. lasers
+    Hitscan { range: f32 },
+    /// Shots fly through the world, e.g. slugs
+    Projectile {
+        speed: f32,
+        /// How long a shot flies before it's gone, in seconds
+        lifetime: f32,
+    },
+}
+
+/// A weapon that can be fired with `FireWeapon`
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct Weapon {
+    pub mode: FireMode,
+    pub damage: Damage,
+    pub ammo: u32,
+    pub max_ammo: u32,
+    /// Never run out of ammo, e.g. for a drone's built-in weapon
+    pub unlimited_ammo: bool,
+    /// How far each shot can be heard, in pixels
+    pub loudness: f32,
+    cooldown: Timer,
+}
+
+impl Weapon {
+    pub fn new(mode: FireMode, damage: Damage, cooldown: f32) -> Self {
+        let mut cooldown = Timer::from_seconds(cooldown, TimerMode::Once);
+        // Weapons start out ready to fire
+        cooldown.tick(cooldown.duration());
+
+        Self {
+            mode,
+            damage,
+            ammo: 12,
+            max_ammo: 12,
+            unlimited_ammo: false,
+            loudness: 320.0,
+            cooldown,
+        }
+    }
+
+    pub fn with_ammo(mut self, max_ammo: u32) -> Self {
+        self.ammo = max_ammo;
+        self.max_ammo = max_ammo;
+        self
+    }
+
+    pub fn with_unlimited_ammo(mut self) -> Self {
+        self.unlimited_ammo = true;
+        self
+    }
+
+    pub fn with_loudness(mut self, loudness: f32) -> Self {
+        self.loudness = loudness;
+        self
+    }
+
+    /// Check if this weapon can be fired right now
+    pub fn is_ready(&self) -> bool {
+        self.ammo > 0 && self.cooldown.finished()
+    }
+
+    pub fn reload(&mut self) {
+        self.ammo = self.max_ammo;
+    }
+
+    /// How long it's been since this weapon was last fired, up to its cooldown
+    pub fn cooldown_elapsed(&self) -> Duration {
+        self.cooldown.elapsed()
+    }
+
+    /// Pick the weapon's cooldown up from `elapsed`, e.g. when loading a saved game
+    pub fn set_cooldown_elapsed(&mut self, elapsed: Duration) {
+        // Ticking, rather than setting the elapsed time, also finishes the cooldown if need be
+        self.cooldown.reset();
+        self.cooldown.tick(elapsed);
+    }
+}
+
+/// A shot in flight
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct Projectile {
+    /// Whoever fired this shot
+    pub shooter: Entity,
+    lifetime: Timer,
+}
+
+/// A short-lived visual effect of a shot, e.g. a tracer
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct ShotEffect(Timer);
+
+/// The flash of a shot being fired
+///
+/// Like everything else about a shot, a muzzle flash is only seen within the player's field of
+/// view; unlike everything else, it's also seen when the player hears the shot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub struct MuzzleFlash {
+    pub shooter: Entity,
+}
+
+/// Send to fire a weapon, in whichever way its owner is facing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub struct FireWeapon {
+    pub shooter: Entity,
+}
+
+/// Shots are only seen within field of view, but are never remembered as ghosts like
+/// `Viewable::Dynamic` entities
+fn in_view_only() -> RenderLayers {
+    RenderLayers::layer(2)
+}
+
+pub fn cool_down_weapons(time: Res<Time>, mut weapon_qry: Query<&mut Weapon>) {
+    for mut weapon in weapon_qry.iter_mut() {
+        if !weapon.cooldown.finished() {
+            weapon.cooldown.tick(time.delta());
+        }
+    }
+}
+
+pub fn player_fire(
+    actions: Res<ActionState>,
+    mut player_qry: Query<(Entity, &mut Weapon), With<Player>>,
+    mut shots: EventWriter<FireWeapon>,
+) {
+    let Ok((player, mut weapon)) = player_qry.get_single_mut() else {
+        return;
+    };
+
+    if actions.just_pressed(Action::Reload) {
+        weapon.reload();
+    }
+    if actions.pressed(Action::Fire) {
+        shots.send(FireWeapon { shooter: player });
+    }
+}
+
+pub fn fire_weapons(
+    mut commands: Commands,
+    rapier_context: Res<RapierContext>,
+    mut shots: EventReader<FireWeapon>,
+    mut shooter_qry: Query<(&GlobalTransform, &mut Weapon)>,
+    mut damages: EventWriter<TakeDamage>,
+    mut noises: EventWriter<Noise>,
+) {
+    for &FireWeapon { shooter } in shots.read() {
+        let Ok((shooter_transform, mut weapon)) = shooter_qry.get_mut(shooter) else {
+            continue;
+        };
+        if !weapon.is_ready() {
+            continue;
+        }
+        if !weapon.unlimited_ammo {
+            weapon.ammo -= 1;
+        }
+        weapon.cooldown.reset();
+
+        let direction = shooter_transform.right().truncate();
+        let origin = shooter_transform.translation().truncate() + direction * MUZZLE_OFFSET;
+
+        match weapon.mode {
+            FireMode::Hitscan { range } => {
+                let filter = QueryFilter::new()
+                    .exclude_sensors()
+                    .exclude_collider(shooter)
+                    .groups(CollisionGroups::new(
+                        PROJECTILE_GROUP,
+                        OPAQUE_GROUP | PLAYER_GROUP | TRANSLUCENT_GROUP,
+                    ));
+                let distance = match rapier_context.cast_ray(origin, direction, range, true, filter)
+                {
+                    Some((target, distance)) => {
+                        damages.send(TakeDamage {
+                            target,
+                            source: Some(shooter),
+                            damage: weapon.damage,
+                        });
+                        distance
+                    }
+                    None => range,
+                };
+                spawn_tracer(&mut commands, origin, direction, distance);
+            }
+            FireMode::Projectile { speed, lifetime } => {
+                commands.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::ORANGE,
+                            custom_size: Some(Vec2::splat(4.0)),
+                            ..Default::default()
+                        },
+                        transform: Transform::from_translation(origin.extend(EFFECT_Z)),
+                        ..Default::default()
+                    },
+                    in_view_only(),
+                    RigidBody::Dynamic,
+                    Collider::ball(2.0),
+                    CollisionGroups::new(
+                        PROJECTILE_GROUP,
+                        OPAQUE_GROUP | PLAYER_GROUP | TRANSLUCENT_GROUP,
+                    ),
+                    Ccd::enabled(),
+                    ActiveEvents::COLLISION_EVENTS,
+                    Velocity::linear(direction * speed),
+                    weapon.damage,
+                    Projectile {
+                        shooter,
+                        lifetime: Timer::from_seconds(lifetime, TimerMode::Once),
+                    },
+                ));
+            }
+        }
+
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::YELLOW,
+                    custom_size: Some(Vec2::splat(6.0)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(origin.extend(EFFECT_Z)),
+                ..Default::default()
+            },
+            in_view_only(),
+            MuzzleFlash { shooter },
+            ShotEffect(Timer::from_seconds(EFFECT_TIME, TimerMode::Once)),
+        ));
+        noises.send(Noise {
+            source: Some(shooter),
+            position: origin,
+            loudness: weapon.loudness,
+        });
+    }
+}
+
+fn spawn_tracer(commands: &mut Commands, origin: Vec2, direction: Vec2, distance: f32) {
+    let center = origin + direction * distance / 2.0;
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::YELLOW,
+                custom_size: Some(Vec2::new(distance, 1.5)),
+                ..Default::default()
+            },
+            transform: Transform::from_translation(center.extend(EFFECT_Z))
+                .with_rotation(Quat::from_rotation_arc_2d(Vec2::X, direction)),
+            ..Default::default()
+        },
+        in_view_only(),
+        ShotEffect(Timer::from_seconds(EFFECT_TIME, TimerMode::Once)),
+    ));
+}
+
+/// Hurt whatever projectiles hit, then get rid of them
+pub fn projectile_hits(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    projectile_qry: Query<(&Projectile, &Damage)>,
+    mut damages: EventWriter<TakeDamage>,
+) {
+    let mut spent = HashSet::new();
+    for collision in collisions.read() {
+        let &CollisionEvent::Started(a, b, flags) = collision else {
+            continue;
+        };
+        // Shots fly straight through sensors, e.g. smoke
+        if flags.contains(CollisionEventFlags::SENSOR) {
+            continue;
+        }
+
+        for (projectile, target) in [(a, b), (b, a)] {
+            let Ok((shot, &damage)) = projectile_qry.get(projectile) else {
+                continue;
+            };
+            // A projectile only hits one thing, even if it touches several at once
+            if !spent.insert(projectile) {
+                continue;
+            }
+
+            damages.send(TakeDamage {
+                target,
+                source: Some(shot.shooter),
+                damage,
+            });
+            commands.entity(projectile).despawn_recursive();
+        }
+    }
+}
+
+pub fn expire_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut projectile_qry: Query<(Entity, &mut Projectile)>,
+) {
+    for (entity, mut projectile) in projectile_qry.iter_mut() {
+        if projectile.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Fade out shot effects, and get rid of them once they're gone
+pub fn fade_shot_effects(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut effect_qry: Query<(Entity, &mut ShotEffect, &mut Sprite)>,
+) {
+    for (entity, mut effect, mut sprite) in effect_qry.iter_mut() {
+        if effect.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            sprite.color.set_a(effect.0.percent_left());
+        }
+    }
+}
+
+/// Show the muzzle flashes of shots the player heard, even outside of their field of view
+///
+/// A flash is spawned by the same shot that makes its noise, but only turns up once its commands
+/// are applied, by which time the player's `Hearing` may have moved on; so whoever was heard is
+/// remembered for a frame, to catch their flashes when they do turn up.
+pub fn reveal_heard_shots(
+    mut commands: Commands,
+    player_qry: Query<&Hearing, With<Player>>,
+    flash_qry: Query<(Entity, &MuzzleFlash), Added<MuzzleFlash>>,
+    mut heard_last_frame: Local<HashSet<Entity>>,
+) {
+    let heard = player_qry
+        .iter()
+        .flat_map(Hearing::heard)
+        .filter_map(|heard| heard.source)
+        .collect::<HashSet<_>>();
+
+    for (flash, &MuzzleFlash { shooter }) in flash_qry.iter() {
+        if heard.contains(&shooter) || heard_last_frame.contains(&shooter) {
+            commands.entity(flash).insert(RenderLayers::default());
+        }
+    }
+
+    *heard_last_frame = heard;
+}
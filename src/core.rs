@@ -1,24 +1,111 @@
 use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    camera::{Follow, MainCamera},
+    fov::ExploredMap,
+    hazard::HazardMap,
+    health::Died,
+    input::{Action, ActionState},
+    lighting::LightMap,
+    map::Rooms,
+    player::Player,
+    save::PendingLoad,
+    ship_systems::ShipSystems,
+    sprites::Sprites,
+    time_control::TimeControl,
+};
 
 mod physics_groups;
 pub use physics_groups::*;
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, States)]
 pub enum GameState {
+    /// Waiting for assets to load
     #[default]
     Loading,
     MainMenu,
     InGame,
+    /// In a game, but with time stopped
+    Paused,
+    /// The player has died; the game is over, but not yet torn down
+    GameOver,
+}
+
+/// Tag component for entities that outlive any one game, and so are left alone when one ends
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
+pub struct Persistent;
+
+/// Move on to the main menu once every sprite has loaded
+pub fn finish_loading(
+    asset_server: Res<AssetServer>,
+    sprites: Option<Res<Sprites>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if sprites.is_some_and(|sprites| sprites.is_loaded(&asset_server)) {
+        next_state.set(GameState::MainMenu);
+    }
 }
 
-/// FIXME: This only exists during development to advance unused states
-pub fn advance_game_state(
+/// Pause the game, or resume it, when `Action::Pause` is pressed
+pub fn toggle_pause(
+    actions: Res<ActionState>,
     state: Res<State<GameState>>,
     mut next_state: ResMut<NextState<GameState>>,
 ) {
+    if !actions.just_pressed(Action::Pause) {
+        return;
+    }
+
     match **state {
-        GameState::Loading => next_state.set(GameState::MainMenu),
-        GameState::MainMenu => next_state.set(GameState::InGame),
-        GameState::InGame => {}
+        GameState::InGame => next_state.set(GameState::Paused),
+        GameState::Paused => next_state.set(GameState::InGame),
+        _ => {}
     }
 }
+
+/// End the game when the player dies
+pub fn end_game_on_death(
+    mut deaths: EventReader<Died>,
+    player_qry: Query<(), With<Player>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if deaths.read().any(|death| player_qry.contains(death.entity)) {
+        next_state.set(GameState::GameOver);
+    }
+}
+
+/// Stop gameplay time and physics, e.g. while paused
+///
+/// They start again as soon as `time_control::update_time_control` runs back in game.
+pub fn stop_time(mut time: ResMut<Time<Virtual>>, mut rapier_config: ResMut<RapierConfiguration>) {
+    time.pause();
+    rapier_config.physics_pipeline_active = false;
+}
+
+/// Top-level entities that only last as long as a single game
+type GameEntities = (Without<Parent>, Without<Persistent>, Without<Window>);
+
+/// Despawn everything from the last game, and reset anything it left behind, ready for a new game
+pub fn teardown_game(
+    mut commands: Commands,
+    entity_qry: Query<Entity, GameEntities>,
+    mut camera_qry: Query<(Entity, &mut Transform), With<MainCamera>>,
+) {
+    for entity in entity_qry.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for (camera, mut transform) in camera_qry.iter_mut() {
+        commands.entity(camera).remove::<Follow>();
+        transform.translation.x = 0.0;
+        transform.translation.y = 0.0;
+    }
+
+    commands.remove_resource::<Rooms>();
+    commands.remove_resource::<ShipSystems>();
+    commands.remove_resource::<HazardMap>();
+    commands.remove_resource::<PendingLoad>();
+    commands.insert_resource(ExploredMap::default());
+    commands.insert_resource(LightMap::default());
+    commands.insert_resource(TimeControl::default());
+}
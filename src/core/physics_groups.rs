@@ -2,3 +2,7 @@ use bevy_rapier2d::prelude::*;
 
 pub const PLAYER_GROUP: Group = Group::GROUP_1;
 pub const OPAQUE_GROUP: Group = Group::GROUP_2;
+/// Colliders that affect sight without blocking it outright, e.g. windows and smoke
+pub const TRANSLUCENT_GROUP: Group = Group::GROUP_3;
+/// Shots fired from weapons
+pub const PROJECTILE_GROUP: Group = Group::GROUP_4;
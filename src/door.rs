@@ -0,0 +1,239 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::OPAQUE_GROUP,
+    fov::Viewable,
+    hearing::SoundDamper,
+    interaction::{Interact, Interactable, InteractionKind},
+    map::{Rooms, TILE_SIZE},
+};
+
+/// How wide a doorway is, in pixels
+pub const DOOR_WIDTH: f32 = 2.0 * TILE_SIZE;
+
+/// How thick a door is, in pixels
+const DOOR_THICKNESS: f32 = TILE_SIZE / 2.0;
+
+/// How long a door takes to fully open or close, in seconds
+const DOOR_OPEN_TIME: f32 = 0.3;
+
+/// How much a closed door muffles noise; far less than a wall does
+const DOOR_DAMPING: f32 = 2.0 * TILE_SIZE;
+
+const DOOR_Z: f32 = 3.0;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DoorState {
+    Open,
+    #[default]
+    Closed,
+    /// Closed, and won't open until it's unlocked
+    Locked,
+    /// Stuck open, and can't be closed again
+    Broken,
+}
+
+/// A door between two rooms
+///
+/// A door blocks movement, sight and (mostly) sound unless it's open or broken. Its two leaves
+/// slide apart along the door's local x-axis as it opens.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct Door {
+    pub state: DoorState,
+    /// How far open the door currently is, from `0.0` (shut) to `1.0` (fully open)
+    openness: f32,
+}
+
+impl Default for Door {
+    fn default() -> Self {
+        Self::new(DoorState::default())
+    }
+}
+
+impl Door {
+    pub fn new(state: DoorState) -> Self {
+        Self {
+            state,
+            openness: if Self::is_passable_state(state) {
+                1.0
+            } else {
+                0.0
+            },
+        }
+    }
+
+    fn is_passable_state(state: DoorState) -> bool {
+        matches!(state, DoorState::Open | DoorState::Broken)
+    }
+
+    /// Check if things can get through this door
+    pub fn is_passable(&self) -> bool {
+        Self::is_passable_state(self.state)
+    }
+
+    /// Open the door if it's closed, or close it if it's open
+    ///
+    /// Returns `false` if the door is locked or broken, and so couldn't be operated.
+    pub fn toggle(&mut self) -> bool {
+        self.state = match self.state {
+            DoorState::Open => DoorState::Closed,
+            DoorState::Closed => DoorState::Open,
+            DoorState::Locked | DoorState::Broken => return false,
+        };
+        true
+    }
+}
+
+/// One of the two sliding halves of a door
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct DoorLeaf {
+    /// Which way this leaf slides as the door opens: `1.0` or `-1.0` along the door's x-axis
+    side: f32,
+}
+
+/// The two rooms a door joins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub struct DoorRooms(pub usize, pub usize);
+
+/// Sent whenever a door starts or stops letting things through
+///
+/// Anything that caches routes around the ship, e.g. pathfinding, should throw out paths through
+/// this door.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub struct DoorChanged {
+    pub door: Entity,
+    pub passable: bool,
+}
+
+/// Spawn a door, centered on `position` and spanning the doorway along `along`
+pub fn spawn_door(
+    commands: &mut Commands,
+    position: Vec2,
+    along: Vec2,
+    state: DoorState,
+) -> Entity {
+    let transform = Transform::from_translation(position.extend(DOOR_Z))
+        .with_rotation(Quat::from_rotation_arc_2d(Vec2::X, along.normalize()));
+
+    commands
+        .spawn((
+            SpatialBundle::from_transform(transform),
+            Door::new(state),
+            Interactable::new(InteractionKind::Open),
+        ))
+        .with_children(|parent| {
+            for side in [-1.0, 1.0] {
+                parent.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::GRAY,
+                            custom_size: Some(Vec2::new(DOOR_WIDTH / 2.0, DOOR_THICKNESS)),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    DoorLeaf { side },
+                    Viewable::Static,
+                ));
+            }
+        })
+        .id()
+}
+
+/// Put a door in every doorway of the ship
+pub fn spawn_doors(mut commands: Commands, rooms: Res<Rooms>) {
+    for (a, b, doorway) in rooms.doorways() {
+        let doorway = doorway.as_rect();
+        // Doors span the wall the rooms share, which is the long side of the doorway
+        let along = if doorway.width() > doorway.height() {
+            Vec2::X
+        } else {
+            Vec2::Y
+        };
+        let door = spawn_door(
+            &mut commands,
+            doorway.center() * TILE_SIZE,
+            along,
+            DoorState::Closed,
+        );
+        commands.entity(door).insert(DoorRooms(a, b));
+    }
+}
+
+/// Open and close doors that are interacted with, by the player or anything else
+pub fn operate_doors(mut interactions: EventReader<Interact>, mut door_qry: Query<&mut Door>) {
+    for interaction in interactions
+        .read()
+        .filter(|interaction| interaction.kind == InteractionKind::Open)
+    {
+        if let Ok(mut door) = door_qry.get_mut(interaction.target) {
+            if !door.toggle() {
+                debug!(
+                    "Door {:?} won't budge: {:?}",
+                    interaction.target, door.state
+                );
+            }
+        }
+    }
+}
+
+/// Make closed doors block movement, sight and sound, and open doors stop blocking them
+pub fn update_door_colliders(
+    mut commands: Commands,
+    door_qry: Query<(Entity, &Door, Option<&Collider>), Changed<Door>>,
+    mut changes: EventWriter<DoorChanged>,
+) {
+    for (entity, door, collider) in door_qry.iter() {
+        let passable = door.is_passable();
+        if passable != collider.is_some() {
+            // Nothing to change; the door is probably just partway through opening or closing
+            continue;
+        }
+
+        if passable {
+            commands
+                .entity(entity)
+                .remove::<(Collider, CollisionGroups, SoundDamper)>();
+        } else {
+            commands.entity(entity).insert((
+                Collider::cuboid(DOOR_WIDTH / 2.0, DOOR_THICKNESS / 2.0),
+                CollisionGroups::new(OPAQUE_GROUP, Group::all()),
+                SoundDamper {
+                    damping: DOOR_DAMPING,
+                },
+            ));
+        }
+        changes.send(DoorChanged {
+            door: entity,
+            passable,
+        });
+    }
+}
+
+/// Slide door leaves open or closed, to match their door's state
+pub fn animate_doors(
+    time: Res<Time>,
+    mut door_qry: Query<&mut Door>,
+    mut leaf_qry: Query<(&Parent, &DoorLeaf, &mut Transform)>,
+) {
+    let step = time.delta_seconds() / DOOR_OPEN_TIME;
+    for mut door in door_qry.iter_mut() {
+        let target = if door.is_passable() { 1.0 } else { 0.0 };
+        if door.openness != target {
+            let openness = door.openness + step.copysign(target - door.openness);
+            door.openness = openness.clamp(0.0, 1.0);
+        }
+    }
+
+    for (parent, leaf, mut transform) in leaf_qry.iter_mut() {
+        if let Ok(door) = door_qry.get(parent.get()) {
+            // Shut, the leaves meet in the middle; fully open, they've slid into the walls
+            let offset = leaf.side * (0.5 + door.openness) * DOOR_WIDTH / 2.0;
+            if transform.translation.x != offset {
+                transform.translation.x = offset;
+            }
+        }
+    }
+}
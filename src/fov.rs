@@ -1,16 +1,154 @@
-use bevy::{prelude::*, render::render_resource::PrimitiveTopology};
+use std::f32::consts::{PI, TAU};
+
+use bevy::{
+    prelude::*,
+    render::{camera::RenderTarget, render_resource::PrimitiveTopology},
+};
 use bevy_rapier2d::prelude::*;
 
+use crate::map::TILE_SIZE;
+
+mod explored;
+pub use explored::{tiles_in_view, ExploredMap, Redraw};
 mod fogofwar;
-pub use fogofwar::setup_fog_of_war;
+pub use fogofwar::{
+    resize_explored_map, setup_fog_of_war, update_explored_chunks, update_fog_overlay,
+    ExploredChunk, FogOverlay,
+};
 mod helpers;
+mod memory;
+pub use memory::{update_ghosts, Ghost};
+mod occluder;
+pub use occluder::{Occluder, Transmittance};
+mod perception;
+pub use perception::{add_visible_entities, update_visible_entities, VisibleEntities};
 mod viewable;
 pub use viewable::{update_viewables, Viewable};
+mod visibility;
+pub use visibility::{cast_view_cone, cast_view_cone_fixed_step};
+
+/// The smallest angle, in radians, ever allowed between adjacent rays
+///
+/// Without a floor, a resolution of zero (or less) would never finish casting rays.
+const MIN_RAY_STEP: f32 = 0.0001;
+
+/// How finely to cast rays when calculating a view cone
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RayResolution {
+    /// A fixed angle, in radians, between adjacent rays
+    Angle(f32),
+    /// The maximum distance between the ends of adjacent rays at the edge of the view
+    ArcLength(f32),
+}
+
+impl Default for RayResolution {
+    fn default() -> Self {
+        Self::Angle(TAU / 360.0)
+    }
+}
+
+impl RayResolution {
+    /// The angle between adjacent rays for a view of the given distance
+    ///
+    /// This is never less than `MIN_RAY_STEP`, however small (or invalid) the resolution.
+    pub fn step(&self, view_distance: f32) -> f32 {
+        let step = match *self {
+            RayResolution::Angle(angle) => angle,
+            RayResolution::ArcLength(length) => length / view_distance,
+        };
+        // NB: f32::max also ignores NaN
+        step.max(MIN_RAY_STEP)
+    }
+}
+
+/// A cone of vision, relative to its viewer's position and facing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewCone {
+    pub distance: f32,
+    /// Half the angle of the cone, in radians; anything of π or more sees all the way around
+    pub half_angle: f32,
+    /// Direction of the cone relative to the viewer's facing, in radians counter-clockwise
+    pub direction: f32,
+}
+
+impl ViewCone {
+    pub fn new(distance: f32, half_angle: f32) -> Self {
+        Self {
+            distance,
+            half_angle,
+            direction: 0.0,
+        }
+    }
+
+    /// A "cone" that sees all the way around its viewer
+    pub fn around(distance: f32) -> Self {
+        Self::new(distance, PI)
+    }
+
+    /// Point this cone in a different direction, relative to the viewer's facing
+    pub fn facing(mut self, direction: f32) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Check if a point at `offset` from the viewer, who is facing `view_direction`, is within this cone
+    pub fn contains(&self, view_direction: Vec2, offset: Vec2) -> bool {
+        if offset.length_squared() > self.distance * self.distance {
+            return false;
+        }
+        if self.half_angle >= PI || offset.length_squared() <= f32::EPSILON {
+            return true;
+        }
+        let direction = Vec2::from_angle(self.direction).rotate(view_direction);
+
+        direction.angle_between(offset).abs() <= self.half_angle
+    }
+}
+
+/// Which faction a viewer shares its vision with
+///
+/// Only viewers allied with the player, i.e. in `VisionFaction::PLAYER`, reveal the map and
+/// `Viewable::Dynamic` entities to the player; everyone else's perception is private, and only
+/// available to game logic through their `VisibleEntities`. Viewers without a `VisionFaction` are
+/// treated as allied with the player.
+///
+/// The faction is read when a `FieldOfView` is added, so should be inserted alongside it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component)]
+pub struct VisionFaction(pub u32);
+
+impl Default for VisionFaction {
+    fn default() -> Self {
+        Self::PLAYER
+    }
+}
+
+impl VisionFaction {
+    /// The player's faction
+    pub const PLAYER: Self = Self(0);
+
+    /// Check if a viewer with the given (optional) faction shares its vision with the player
+    pub fn is_allied(faction: Option<&VisionFaction>) -> bool {
+        faction.copied().unwrap_or_default() == Self::PLAYER
+    }
+}
 
+/// A viewer's field of view
+///
+/// This is primarily a single cone of `view_distance` and (half-)`view_angle` centered on the
+/// viewer's facing, optionally joined by any number of `extra_cones`, e.g. for peripheral vision or
+/// rear-facing sensors; the union of all of them is treated as a single view.
 #[derive(Debug, Clone, Component)]
 pub struct FieldOfView {
     pub view_distance: f32,
     pub view_angle: f32,
+    pub extra_cones: Vec<ViewCone>,
+    pub resolution: RayResolution,
+    /// Add more rays between adjacent rays whose hits differ in distance by more than this
+    ///
+    /// Set to `None` to disable adaptive refinement.
+    pub refine_distance: Option<f32>,
+    /// How far this viewer can see things regardless of how well lit they are
+    pub dark_vision: f32,
     pub mesh: Handle<Mesh>,
 }
 
@@ -19,19 +157,59 @@ impl FieldOfView {
         Self {
             view_distance,
             view_angle,
+            extra_cones: Vec::new(),
+            resolution: RayResolution::default(),
+            refine_distance: Some(TILE_SIZE),
+            dark_vision: TILE_SIZE * 2.0,
             mesh: Handle::default(),
         }
     }
+
+    pub fn with_cone(mut self, cone: ViewCone) -> Self {
+        self.extra_cones.push(cone);
+        self
+    }
+
+    pub fn with_dark_vision(mut self, dark_vision: f32) -> Self {
+        self.dark_vision = dark_vision;
+        self
+    }
+
+    pub fn with_resolution(mut self, resolution: RayResolution) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    pub fn with_refine_distance(mut self, refine_distance: Option<f32>) -> Self {
+        self.refine_distance = refine_distance;
+        self
+    }
+
+    /// All of the cones making up this view, starting with the primary one
+    pub fn cones(&self) -> impl Iterator<Item = ViewCone> + '_ {
+        std::iter::once(ViewCone::new(self.view_distance, self.view_angle))
+            .chain(self.extra_cones.iter().copied())
+    }
+
+    /// The furthest distance this view can see
+    pub fn max_distance(&self) -> f32 {
+        self.cones().map(|cone| cone.distance).fold(0.0, f32::max)
+    }
 }
 
 pub fn add_fov(
-    mut fov_query: Query<(Entity, &mut FieldOfView), Added<FieldOfView>>,
+    mut fov_query: Query<(Entity, &mut FieldOfView, Option<&VisionFaction>), Added<FieldOfView>>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut images: ResMut<Assets<Image>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    for (entity, mut fov) in fov_query.iter_mut() {
+    for (entity, mut fov, faction) in fov_query.iter_mut() {
+        if !VisionFaction::is_allied(faction) {
+            // Only our allies' vision gets rendered
+            continue;
+        }
+
         let image = helpers::make_fov_texture(&fov);
 
         // Create a mesh to render the field of view to
@@ -50,24 +228,76 @@ pub fn add_fov(
     }
 }
 
+/// Resize the textures a field of view is rendered to whenever how far it can see changes
+pub(crate) fn resize_fov(
+    viewer_qry: Query<(Entity, &FieldOfView), Changed<FieldOfView>>,
+    camera_qry: Query<(&Camera, &helpers::FovLayerOf)>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for (viewer, fov) in viewer_qry.iter() {
+        let size = helpers::fov_texture_size(fov);
+        for (camera, _) in camera_qry.iter().filter(|(_, of)| of.0 == viewer) {
+            let RenderTarget::Image(target) = &camera.target else {
+                continue;
+            };
+            if let Some(image) = images.get_mut(target) {
+                if image.texture_descriptor.size != size {
+                    image.resize(size);
+                }
+            }
+        }
+    }
+}
+
+/// Stop rendering the field of view of anything that's lost its `FieldOfView`, or been despawned
+pub(crate) fn remove_fov(
+    mut commands: Commands,
+    mut removed: RemovedComponents<FieldOfView>,
+    layer_qry: Query<(Entity, &helpers::FovLayerOf)>,
+) {
+    for viewer in removed.read() {
+        for (layer, _) in layer_qry.iter().filter(|(_, of)| of.0 == viewer) {
+            commands.entity(layer).despawn();
+        }
+    }
+}
+
 pub fn update_fov(
     rapier_context: Res<RapierContext>,
-    viewer_qry: Query<(Entity, &GlobalTransform, &FieldOfView)>,
+    occluder_qry: Query<(&Occluder, &GlobalTransform)>,
+    viewer_qry: Query<(
+        Entity,
+        &GlobalTransform,
+        &FieldOfView,
+        Option<&VisionFaction>,
+    )>,
     mut meshes: ResMut<Assets<Mesh>>,
+    mut explored: ResMut<ExploredMap>,
 ) {
-    for (viewer, viewer_transform, viewer_fov) in viewer_qry.iter() {
-        if let Some(mesh) = meshes.get_mut(&viewer_fov.mesh) {
-            let viewer_pos = viewer_transform.translation().truncate();
+    for (viewer, viewer_transform, viewer_fov, faction) in viewer_qry.iter() {
+        if !VisionFaction::is_allied(faction) {
+            // Hostile viewers neither reveal the map nor show us what they see
+            continue;
+        }
+
+        let viewer_pos = viewer_transform.translation().truncate();
 
-            let points = helpers::cast_view_cone(
-                viewer,
-                viewer_pos,
-                viewer_fov,
-                viewer_transform.right().truncate(),
-                &rapier_context,
-            );
+        let points = cast_view_cone(
+            viewer,
+            viewer_pos,
+            viewer_fov,
+            viewer_transform.right().truncate(),
+            &rapier_context,
+            &occluder_qry,
+        );
+
+        // Record what we've explored, only triggering change detection if we've found something new
+        if explored.bypass_change_detection().reveal_view(&points) {
+            explored.set_changed();
+        }
 
-            // Update our mesh
+        // Update our mesh
+        if let Some(mesh) = meshes.get_mut(&viewer_fov.mesh) {
             helpers::update_view_mesh(mesh, &points, viewer_fov, viewer_pos);
         }
     }
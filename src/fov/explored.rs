@@ -0,0 +1,303 @@
+use bevy::{prelude::*, utils::HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::map::{tile_at, Rooms, TileGrid, TILE_SIZE};
+
+/// Default extent of the explored map, in tiles; this matches the size of the fog of war overlay
+const DEFAULT_EXTENT: i32 = 128;
+
+/// Size, in tiles, of the chunks the fog of war is redrawn in as tiles are explored
+const REDRAW_CHUNK_TILES: i32 = 16;
+
+/// The tiles that have been explored, i.e. have at some point been within a field of view
+///
+/// This is the authoritative record of what has been explored; the fog of war rendering is
+/// derived from it, rather than the other way around, so that it can be queried and saved.
+#[derive(Debug, Clone, Resource, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ExploredMap {
+    tiles: TileGrid<bool>,
+    /// Chunks, in units of `REDRAW_CHUNK_TILES` from `bounds.min`, with newly explored tiles
+    ///
+    /// `None` means everything needs redrawing, e.g. when the map is new or has just been loaded.
+    #[serde(skip)]
+    dirty: Option<HashSet<IVec2>>,
+}
+
+/// What needs redrawing after exploring more of an `ExploredMap`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Redraw {
+    /// The whole map
+    All,
+    /// Only the tiles within each of these rects
+    Chunks(Vec<IRect>),
+}
+
+/// Two maps are the same if they've explored the same tiles, however much of them has been drawn
+impl PartialEq for ExploredMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.tiles == other.tiles
+    }
+}
+
+impl Eq for ExploredMap {}
+
+impl Default for ExploredMap {
+    fn default() -> Self {
+        Self::new(Self::default_bounds())
+    }
+}
+
+impl ExploredMap {
+    /// Create a new, entirely unexplored, map covering `bounds`
+    pub fn new(bounds: IRect) -> Self {
+        Self {
+            tiles: TileGrid::new(bounds, false),
+            dirty: None,
+        }
+    }
+
+    /// The tiles covered by a default map
+    pub fn default_bounds() -> IRect {
+        IRect::from_center_size(IVec2::ZERO, IVec2::splat(DEFAULT_EXTENT))
+    }
+
+    /// The tiles covered by this map
+    pub fn bounds(&self) -> IRect {
+        self.tiles.bounds()
+    }
+
+    /// Whether or not `tile` has been explored
+    ///
+    /// Tiles outside of the map's bounds are never explored.
+    pub fn is_explored(&self, tile: IVec2) -> bool {
+        self.tiles.get(tile).copied().unwrap_or(false)
+    }
+
+    /// Whether or not the tile containing the world-space `point` has been explored
+    pub fn is_point_explored(&self, point: Vec2) -> bool {
+        self.is_explored(tile_at(point))
+    }
+
+    /// Whether or not any tile within `rect` has been explored
+    pub fn any_explored(&self, rect: IRect) -> bool {
+        (rect.min.y..rect.max.y)
+            .any(|y| (rect.min.x..rect.max.x).any(|x| self.is_explored(IVec2::new(x, y))))
+    }
+
+    /// Whether or not any part of room number `room` has been seen
+    pub fn has_seen_room(&self, rooms: &Rooms, room: usize) -> bool {
+        rooms
+            .get(room)
+            .map(|&room| self.any_explored(room))
+            .unwrap_or(false)
+    }
+
+    /// Mark `tile` as explored, returning `true` if it was not already explored
+    pub fn reveal(&mut self, tile: IVec2) -> bool {
+        let min = self.bounds().min;
+        match self.tiles.get_mut(tile) {
+            Some(explored) if !*explored => {
+                *explored = true;
+                if let Some(dirty) = &mut self.dirty {
+                    dirty.insert((tile - min) / REDRAW_CHUNK_TILES);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Mark every tile covered by a view polygon as explored
+    ///
+    /// See `tiles_in_view` for details on the polygon. Returns `true` if any tiles were newly
+    /// explored.
+    pub fn reveal_view(&mut self, points: &[Vec2]) -> bool {
+        // Reveal every tile, even once one is found to be new
+        let mut revealed = false;
+        for tile in tiles_in_view(points, self.bounds()) {
+            revealed |= self.reveal(tile);
+        }
+        revealed
+    }
+
+    /// Find what's been explored since the last call, so only that needs to be redrawn
+    pub fn take_redraw(&mut self) -> Redraw {
+        let Some(dirty) = self.dirty.replace(HashSet::new()) else {
+            return Redraw::All;
+        };
+
+        let bounds = self.bounds();
+        let chunks = dirty
+            .into_iter()
+            .map(|chunk| {
+                let min = bounds.min + chunk * REDRAW_CHUNK_TILES;
+                IRect::from_corners(min, (min + REDRAW_CHUNK_TILES).min(bounds.max))
+            })
+            .collect();
+        Redraw::Chunks(chunks)
+    }
+}
+
+/// Find every tile within `bounds` covered by a view polygon
+///
+/// The polygon is a triangle fan as produced by `cast_view_cone`, i.e. the first point is the
+/// viewer's position and the remaining points trace the edge of the view. A tile is covered if its
+/// center is within view.
+pub fn tiles_in_view(points: &[Vec2], bounds: IRect) -> HashSet<IVec2> {
+    let mut tiles = HashSet::new();
+    let Some((&origin, edge)) = points.split_first() else {
+        return tiles;
+    };
+    let origin = origin / TILE_SIZE;
+
+    for pair in edge.windows(2) {
+        let triangle = [origin, pair[0] / TILE_SIZE, pair[1] / TILE_SIZE];
+
+        let min = triangle[0]
+            .min(triangle[1])
+            .min(triangle[2])
+            .floor()
+            .as_ivec2();
+        let max = triangle[0]
+            .max(triangle[1])
+            .max(triangle[2])
+            .ceil()
+            .as_ivec2();
+        // No need to look at anything outside of our bounds
+        let min = min.max(bounds.min);
+        let max = max.min(bounds.max);
+
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let tile = IVec2::new(x, y);
+                let center = tile.as_vec2() + Vec2::splat(0.5);
+                if triangle_contains(triangle, center) {
+                    tiles.insert(tile);
+                }
+            }
+        }
+    }
+
+    tiles
+}
+
+/// Check if `point` lies within (or on the edge of) `triangle`, regardless of its winding
+fn triangle_contains([a, b, c]: [Vec2; 3], point: Vec2) -> bool {
+    let d1 = (b - a).perp_dot(point - a);
+    let d2 = (c - b).perp_dot(point - b);
+    let d3 = (a - c).perp_dot(point - c);
+
+    let has_neg = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+    let has_pos = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+
+    !(has_neg && has_pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fov::resize_explored_map;
+
+    /// A view polygon, from the origin, covering the square of tiles `-extent..extent` on each axis
+    fn square_view(extent: f32) -> Vec<Vec2> {
+        let corner = extent * TILE_SIZE;
+        vec![
+            Vec2::ZERO,
+            Vec2::new(-corner, -corner),
+            Vec2::new(corner, -corner),
+            Vec2::new(corner, corner),
+            Vec2::new(-corner, corner),
+            Vec2::new(-corner, -corner),
+        ]
+    }
+
+    #[test]
+    fn reveal_view_explores_tiles_in_view() {
+        let mut explored = ExploredMap::default();
+
+        assert!(explored.reveal_view(&square_view(2.0)));
+        for y in -2..2 {
+            for x in -2..2 {
+                assert!(explored.is_explored(IVec2::new(x, y)), "({x}, {y})");
+            }
+        }
+        assert!(!explored.is_explored(IVec2::new(2, 0)));
+        assert!(!explored.is_explored(IVec2::new(0, -3)));
+        assert!(explored.any_explored(IRect::new(1, 1, 3, 3)));
+        assert!(!explored.any_explored(IRect::new(2, 2, 4, 4)));
+
+        // Nothing new to see the second time around
+        assert!(!explored.reveal_view(&square_view(2.0)));
+        assert!(!explored.reveal_view(&[]));
+    }
+
+    #[test]
+    fn is_explored_respects_bounds() {
+        let mut explored = ExploredMap::new(IRect::new(-4, -2, 4, 2));
+
+        // min is inclusive, max is exclusive
+        assert!(explored.reveal(IVec2::new(-4, -2)));
+        assert!(explored.is_explored(IVec2::new(-4, -2)));
+        assert!(!explored.reveal(IVec2::new(4, 0)));
+        assert!(!explored.is_explored(IVec2::new(4, 0)));
+        assert!(!explored.reveal(IVec2::new(0, 2)));
+        assert!(!explored.is_explored(IVec2::new(0, 2)));
+
+        // Views reaching past the bounds only explore what's within them
+        assert!(explored.reveal_view(&square_view(8.0)));
+        assert!(explored.is_explored(IVec2::new(3, 1)));
+        assert!(!explored.is_explored(IVec2::new(-5, 0)));
+        assert!(!explored.is_explored(IVec2::new(0, -3)));
+    }
+
+    #[test]
+    fn only_newly_explored_chunks_are_redrawn() {
+        let mut explored = ExploredMap::new(IRect::new(-20, -20, 20, 20));
+        assert_eq!(explored.take_redraw(), Redraw::All);
+        assert_eq!(explored.take_redraw(), Redraw::Chunks(Vec::new()));
+
+        // Chunks are measured from the corner of the map, and cut short at its far edge
+        explored.reveal(IVec2::new(-20, -20));
+        explored.reveal(IVec2::new(-5, -5));
+        explored.reveal(IVec2::new(19, 19));
+        let Redraw::Chunks(mut chunks) = explored.take_redraw() else {
+            panic!("expected only chunks to be redrawn");
+        };
+        chunks.sort_by_key(|chunk| chunk.min.to_array());
+        assert_eq!(
+            chunks,
+            [IRect::new(-20, -20, -4, -4), IRect::new(12, 12, 20, 20)]
+        );
+
+        // Revealing what's already been seen needs no redraw
+        explored.reveal(IVec2::new(19, 19));
+        assert_eq!(explored.take_redraw(), Redraw::Chunks(Vec::new()));
+    }
+
+    #[test]
+    fn resizing_fits_the_ship_and_forgets_exploration() {
+        let rooms = Rooms::new(vec![
+            IRect::new(-40, -8, -20, 8),
+            IRect::new(-20, -4, 60, 4),
+        ]);
+        let mut explored = ExploredMap::default();
+        explored.reveal(IVec2::ZERO);
+
+        let mut app = App::new();
+        app.insert_resource(explored)
+            .insert_resource(rooms.clone())
+            .add_systems(Update, resize_explored_map);
+        app.update();
+
+        let explored = app.world.resource::<ExploredMap>();
+        let bounds = explored.bounds();
+        assert_eq!(bounds.union(rooms.bounds()), bounds);
+        assert_ne!(bounds, ExploredMap::default_bounds());
+        assert!(!explored.is_explored(IVec2::ZERO));
+
+        // The far end of the ship was out of bounds before, but not any more
+        let mut explored = explored.clone();
+        assert!(explored.reveal(IVec2::new(66, 0)));
+    }
+}
@@ -10,36 +10,154 @@ use bevy::{
     },
 };
 
-use super::Viewable;
+use super::{ExploredMap, Redraw, Viewable};
+use crate::{
+    map::{Rooms, TILE_SIZE},
+    sprites::Sprites,
+};
+
+/// Opacity of the fog over areas that have never been explored
+const UNEXPLORED_ALPHA: u8 = 255;
+/// Opacity of the fog over areas that have been explored but are not currently in view
+const EXPLORED_ALPHA: u8 = 217; // ~85%
+
+/// How many tiles to leave around the ship when sizing the fog of war
+const SHIP_MARGIN: i32 = 8;
+
+/// Size, in tiles, of each chunk of the explored map's render targets
+///
+/// At `TILE_SIZE` 16, this makes each chunk's texture 2048×2048.
+const CHUNK_TILES: i32 = 128;
+
+/// Tag component for the fog of war overlay
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
+pub struct FogOverlay;
+
+/// Component for the sprites and cameras rendering a chunk of the explored map, covering the tiles
+/// within its bounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub struct ExploredChunk(pub IRect);
 
 pub fn setup_fog_of_war(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    sprites: Res<Sprites>,
     mut images: ResMut<Assets<Image>>,
+    explored: Res<ExploredMap>,
 ) {
     // Spawn a background image
     commands.spawn((
         SpriteBundle {
-            texture: asset_server.load("nebula.png"),
+            texture: sprites.nebula.clone(),
             ..Default::default()
         },
         Viewable::Static,
     ));
-    // Overlay a "fog of war"
-    commands.spawn(SpriteBundle {
-        sprite: Sprite {
-            color: Color::BLACK.with_a(0.85),
-            custom_size: Some(Vec2::splat(2048.0)),
+    // Overlay a "fog of war", darker over areas we've never explored
+    let bounds = explored.bounds().as_rect();
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(bounds.size() * TILE_SIZE),
+                ..Default::default()
+            },
+            texture: images.add(make_fog_image(&explored)),
+            transform: Transform::from_translation((bounds.center() * TILE_SIZE).extend(100.0)),
             ..Default::default()
         },
-        transform: Transform::from_xyz(0.0, 0.0, 100.0),
-        ..Default::default()
-    });
+        FogOverlay,
+    ));
+}
+
+/// Size the explored map to fit a newly generated ship
+///
+/// A new ship is a new map, so this also forgets everything that's been explored.
+pub fn resize_explored_map(rooms: Option<Res<Rooms>>, mut explored: ResMut<ExploredMap>) {
+    let Some(rooms) = rooms else {
+        return;
+    };
+    if !rooms.is_changed() || rooms.is_empty() {
+        return;
+    }
+
+    *explored = ExploredMap::new(rooms.bounds().inset(SHIP_MARGIN));
+}
+
+/// Keep the fog of war overlay in sync with the explored map
+///
+/// Only the chunks with newly explored tiles are redrawn, unless the whole map has changed.
+pub fn update_fog_overlay(
+    mut explored: ResMut<ExploredMap>,
+    mut overlay_qry: Query<(&Handle<Image>, &mut Sprite, &mut Transform), With<FogOverlay>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if !explored.is_changed() {
+        return;
+    }
+    // Drawing what's been explored doesn't change it
+    let redraw = explored.bypass_change_detection().take_redraw();
+
+    let bounds = explored.bounds();
+    for (overlay, mut sprite, mut transform) in overlay_qry.iter_mut() {
+        let Some(image) = images.get_mut(overlay) else {
+            continue;
+        };
+        match &redraw {
+            Redraw::Chunks(chunks) if image.size() == bounds.size().as_uvec2() => {
+                for &chunk in chunks {
+                    draw_fog(image, &explored, chunk);
+                }
+            }
+            _ => {
+                *image = make_fog_image(&explored);
+                // The map may also have been resized
+                let bounds = bounds.as_rect();
+                sprite.custom_size = Some(bounds.size() * TILE_SIZE);
+                transform.translation =
+                    (bounds.center() * TILE_SIZE).extend(transform.translation.z);
+            }
+        }
+    }
+}
+
+/// Make sure there are render targets covering the whole explored map
+///
+/// The explored map is split into chunks of `CHUNK_TILES` so that very large ships don't need a
+/// single, impossibly large texture; when the map is resized, every chunk is replaced.
+pub fn update_explored_chunks(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    explored: Res<ExploredMap>,
+    chunk_qry: Query<Entity, With<ExploredChunk>>,
+    mut covered: Local<Option<IRect>>,
+) {
+    let bounds = explored.bounds();
+    if *covered == Some(bounds) && !chunk_qry.is_empty() {
+        // We're already covered
+        return;
+    }
+
+    for entity in chunk_qry.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for y in (bounds.min.y..bounds.max.y).step_by(CHUNK_TILES as usize) {
+        for x in (bounds.min.x..bounds.max.x).step_by(CHUNK_TILES as usize) {
+            let min = IVec2::new(x, y);
+            let chunk = IRect::from_corners(min, (min + CHUNK_TILES).min(bounds.max));
+            spawn_explored_chunk(chunk, &mut commands, &mut images);
+        }
+    }
+    *covered = Some(bounds);
+}
+
+/// Spawn the render target, sprite, and camera that record what's been seen within `chunk`
+fn spawn_explored_chunk(chunk: IRect, commands: &mut Commands, images: &mut Assets<Image>) {
+    let rect = chunk.as_rect();
+    let center = rect.center() * TILE_SIZE;
 
     // Spawn an empty texture we'll draw the "explored" map to
     let size = Extent3d {
-        width: 2048,
-        height: 2048,
+        width: (rect.width() * TILE_SIZE) as u32,
+        height: (rect.height() * TILE_SIZE) as u32,
         ..Default::default()
     };
     // This is the texture that the seen map will be rendered to.
@@ -62,25 +180,66 @@ pub fn setup_fog_of_war(
     let render_target = images.add(image);
     commands.spawn((
         SpriteBundle {
-            transform: Transform::from_xyz(0.0, 0.0, 1.0),
+            transform: Transform::from_translation(center.extend(1.0)),
             texture: render_target.clone(),
             ..Default::default()
         },
         RenderLayers::default().with(3), // layers 0 and 3 make it visible only to default and "explorer" cameras
+        ExploredChunk(chunk),
     ));
     // Spawn a camera that will be used to reveal the explored map
-    commands.spawn((
-        Camera2dBundle {
-            camera_2d: Camera2d {
-                clear_color: ClearColorConfig::Custom(Color::BLACK),
-            },
-            camera: Camera {
-                order: -2,
-                target: RenderTarget::Image(render_target),
-                ..Default::default()
-            },
+    let mut camera = Camera2dBundle {
+        camera_2d: Camera2d {
+            clear_color: ClearColorConfig::Custom(Color::BLACK),
+        },
+        camera: Camera {
+            order: -2,
+            target: RenderTarget::Image(render_target),
             ..Default::default()
         },
-        RenderLayers::layer(3),
-    ));
+        ..Default::default()
+    };
+    // Center the camera on its chunk, keeping its default depth
+    camera.transform.translation.x = center.x;
+    camera.transform.translation.y = center.y;
+    commands.spawn((camera, RenderLayers::layer(3), ExploredChunk(chunk)));
+}
+
+/// Create the fog of war texture from the explored map, with one pixel per tile
+fn make_fog_image(explored: &ExploredMap) -> Image {
+    let bounds = explored.bounds();
+    let size = Extent3d {
+        width: bounds.width() as u32,
+        height: bounds.height() as u32,
+        ..Default::default()
+    };
+
+    let mut image = Image::new_fill(
+        size,
+        TextureDimension::D2,
+        &[0, 0, 0, UNEXPLORED_ALPHA],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    draw_fog(&mut image, explored, bounds);
+
+    image
+}
+
+/// Redraw the pixels of the fog of war texture for every tile within `rect`
+fn draw_fog(image: &mut Image, explored: &ExploredMap, rect: IRect) {
+    let bounds = explored.bounds();
+    let rect = rect.intersect(bounds);
+    for y in rect.min.y..rect.max.y {
+        // Flip y: In Bevy space, y points up; in texture space, y points down!
+        let row = (bounds.max.y - 1 - y) * bounds.width();
+        for x in rect.min.x..rect.max.x {
+            let alpha = if explored.is_explored(IVec2::new(x, y)) {
+                EXPLORED_ALPHA
+            } else {
+                UNEXPLORED_ALPHA
+            };
+            let pixel = (row + x - bounds.min.x) as usize * 4;
+            image.data[pixel + 3] = alpha;
+        }
+    }
 }
@@ -1,5 +1,3 @@
-use std::f32::consts::TAU;
-
 use bevy::{
     core_pipeline::clear_color::ClearColorConfig,
     prelude::*,
@@ -13,15 +11,15 @@ use bevy::{
     },
     sprite::MaterialMesh2dBundle,
 };
-use bevy_rapier2d::prelude::*;
 use itertools::Itertools;
 
-use crate::{camera::Follow, core::OPAQUE_GROUP};
+use crate::camera::Follow;
 
 use super::FieldOfView;
 
-/// How much to rotate each ray when calculating view cone
-const RAY_ROTATION_ANGLE: f32 = TAU / 360.0;
+/// Marks the entities rendering a viewer's field of view, so they can be cleaned up with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub(crate) struct FovLayerOf(pub(crate) Entity);
 
 enum FovLayer {
     Static,
@@ -51,13 +49,18 @@ impl FovLayer {
     }
 }
 
-pub(super) fn make_fov_texture(fov: &FieldOfView) -> Image {
-    let extent = fov.view_distance as u32 * 2;
-    let size = Extent3d {
+/// The size of the texture a field of view is rendered to
+pub(super) fn fov_texture_size(fov: &FieldOfView) -> Extent3d {
+    let extent = fov.max_distance() as u32 * 2;
+    Extent3d {
         width: extent,
         height: extent,
         ..Default::default()
-    };
+    }
+}
+
+pub(super) fn make_fov_texture(fov: &FieldOfView) -> Image {
+    let size = fov_texture_size(fov);
 
     // This is the texture that the field of view cone will be rendered to.
     // Need to do this manually to specify the usages
@@ -100,6 +103,7 @@ pub(super) fn make_fov_layers(
                 ..Default::default()
             },
             layer.texture_render_layers(),
+            FovLayerOf(viewer),
         ));
         commands.spawn((
             Camera2dBundle {
@@ -115,50 +119,19 @@ pub(super) fn make_fov_layers(
             },
             Follow(viewer),
             layer.camera_render_layers(),
+            FovLayerOf(viewer),
         ));
     }
 }
 
-pub(super) fn cast_view_cone(
-    viewer: Entity,
-    origin: Vec2,
-    fov: &FieldOfView,
-    view_direction: Vec2,
-    rapier_context: &RapierContext,
-) -> Vec<Vec2> {
-    let filter = QueryFilter::new()
-        .groups(CollisionGroups::new(Group::all(), OPAQUE_GROUP))
-        .exclude_collider(viewer);
-    let solid = true;
-
-    let mut ray = Vec2::from_angle(-fov.view_angle).rotate(view_direction);
-    let num_rays = (fov.view_angle * 2.0 / RAY_ROTATION_ANGLE).floor() as usize;
-
-    // Iterate inclusively to ensure we include both edges of our view cone
-    // OPTIMIZATION: We could collect corners and only cast rays to those, and then calculate the intersections
-    // of the arc with each line segment and cast rays to those, to cast a lot fewer rays
-    let mut points = vec![origin];
-    points.extend((0..=num_rays).map(|_| {
-        let toi = rapier_context
-            .cast_ray(origin, ray, fov.view_distance, solid, filter)
-            .map(|(_, toi)| toi)
-            .unwrap_or(fov.view_distance);
-        let end = origin + ray * toi;
-        ray = ray.rotate(Vec2::from_angle(RAY_ROTATION_ANGLE));
-
-        end
-    }));
-
-    points
-}
-
 pub(super) fn update_view_mesh(mesh: &mut Mesh, points: &[Vec2], fov: &FieldOfView, origin: Vec2) {
     // Note that UV coordinates are for the entire square that our view disc is inscribed within, not just the cone itself!
-    let uv_origin = Vec2::new(origin.x - fov.view_distance, origin.y - fov.view_distance);
+    let view_distance = fov.max_distance();
+    let uv_origin = Vec2::new(origin.x - view_distance, origin.y - view_distance);
     let (mesh_points, uv_points): (Vec<_>, Vec<_>) = points
         .iter()
         .map(|&point| {
-            let uv = (point - uv_origin) / (fov.view_distance * 2.0);
+            let uv = (point - uv_origin) / (view_distance * 2.0);
 
             (
                 [point.x, point.y, 0.0],
@@ -0,0 +1,214 @@
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use bevy_rapier2d::prelude::*;
+
+use super::{visibility, FieldOfView, Occluder, Viewable, VisibleEntities, VisionFaction};
+use crate::map::TILE_SIZE;
+
+/// How opaque ghosts are compared to the entity they remember
+const GHOST_ALPHA: f32 = 0.35;
+
+/// Ghosts sit above the fog of war, but beneath anything currently in view
+const GHOST_Z: f32 = 150.0;
+
+/// How far an entity must have moved from its ghost before seeing the spot shows it's gone
+const GHOST_CLEAR_DISTANCE: f32 = TILE_SIZE / 2.0;
+
+/// A faded marker of where the player last saw a `Viewable::Dynamic` entity
+///
+/// Ghosts are cleared once the entity is seen again, or once the spot it was last seen at is seen
+/// and the entity has really moved on from it; an entity that's merely gone unseen where it stood,
+/// e.g. in the dark, keeps its ghost.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct Ghost {
+    /// The entity this ghost remembers
+    pub entity: Entity,
+    /// When the entity was last seen, in seconds since startup
+    pub last_seen: f32,
+}
+
+/// Everything a `Viewable` needs for a ghost to be left of it
+type ViewableComponents = (
+    &'static Viewable,
+    &'static GlobalTransform,
+    Option<&'static Handle<Image>>,
+    Option<&'static Sprite>,
+);
+
+/// What the player and their allies can see
+#[derive(SystemParam)]
+pub struct AlliedSight<'w, 's> {
+    rapier_context: Res<'w, RapierContext>,
+    viewer_qry: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static GlobalTransform,
+            &'static FieldOfView,
+            &'static VisibleEntities,
+            Option<&'static VisionFaction>,
+        ),
+    >,
+    occluder_qry: Query<'w, 's, (&'static Occluder, &'static GlobalTransform)>,
+}
+
+impl AlliedSight<'_, '_> {
+    /// Every entity currently seen by the player or their allies
+    fn visible(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.viewer_qry
+            .iter()
+            .filter(|(.., faction)| VisionFaction::is_allied(*faction))
+            .flat_map(|(_, _, _, visible, _)| visible.iter().copied())
+    }
+
+    /// Check if the player or any of their allies can see `target` at `position`
+    fn can_see(&self, target: Entity, position: Vec2) -> bool {
+        self.viewer_qry
+            .iter()
+            .filter(|(.., faction)| VisionFaction::is_allied(*faction))
+            .any(|(viewer, viewer_transform, fov, ..)| {
+                visibility::has_line_of_sight(
+                    viewer,
+                    viewer_transform.translation().truncate(),
+                    fov,
+                    viewer_transform.right().truncate(),
+                    target,
+                    position,
+                    &self.rapier_context,
+                    &self.occluder_qry,
+                )
+            })
+    }
+}
+
+pub fn update_ghosts(
+    mut commands: Commands,
+    time: Res<Time>,
+    sight: AlliedSight,
+    viewable_qry: Query<ViewableComponents>,
+    ghost_qry: Query<(Entity, &Ghost, &GlobalTransform)>,
+    mut seen_last_frame: Local<HashMap<Entity, (GlobalTransform, f32)>>,
+) {
+    // Every dynamic entity the player (or their allies) can currently see, and where
+    let now = time.elapsed_seconds();
+    let mut seen = HashMap::new();
+    for entity in sight.visible() {
+        if let Ok((Viewable::Dynamic, &transform, ..)) = viewable_qry.get(entity) {
+            seen.insert(entity, (transform, now));
+        }
+    }
+
+    for (ghost, &Ghost { entity, .. }, ghost_transform) in ghost_qry.iter() {
+        // We know where this entity is now, so we don't need a ghost of it
+        if seen.contains_key(&entity) {
+            commands.entity(ghost).despawn_recursive();
+            continue;
+        }
+
+        // If the entity is still where we left it, seeing the spot doesn't tell us otherwise
+        let position = ghost_transform.translation().truncate();
+        let still_there = viewable_qry
+            .get(entity)
+            .map(|(_, transform, ..)| {
+                transform.translation().truncate().distance(position) <= GHOST_CLEAR_DISTANCE
+            })
+            .unwrap_or(false);
+        if still_there {
+            continue;
+        }
+
+        // If we can see where it was, we can see it's not there anymore
+        if sight.can_see(ghost, position) {
+            commands.entity(ghost).despawn_recursive();
+        }
+    }
+
+    // Leave a ghost behind for everything that's just gone out of sight
+    for (&entity, &(transform, last_seen)) in seen_last_frame.iter() {
+        if seen.contains_key(&entity) {
+            continue;
+        }
+        // If it's gone entirely, there's nothing to remember it by
+        let Ok((_, _, texture, sprite)) = viewable_qry.get(entity) else {
+            continue;
+        };
+
+        let mut sprite = sprite.cloned().unwrap_or_default();
+        sprite.color = sprite.color.with_a(sprite.color.a() * GHOST_ALPHA);
+        let transform = transform.compute_transform();
+        commands.spawn((
+            SpriteBundle {
+                sprite,
+                texture: texture.cloned().unwrap_or_default(),
+                transform: transform
+                    .with_translation(transform.translation.truncate().extend(GHOST_Z)),
+                ..Default::default()
+            },
+            Ghost { entity, last_seen },
+        ));
+    }
+
+    *seen_last_frame = seen;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+
+    /// An app with a single viewer at the origin, looking at (but, as far as `VisibleEntities` is
+    /// concerned, not seeing) a drone, which has left a ghost right where it is
+    fn setup() -> (App, Entity, Entity) {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(RapierContext::default())
+            .add_systems(Update, update_ghosts);
+
+        app.world.spawn((
+            GlobalTransform::IDENTITY,
+            FieldOfView::new(TILE_SIZE * 10.0, PI),
+            VisibleEntities::default(),
+        ));
+        let position = Vec3::new(TILE_SIZE * 4.0, 0.0, 0.0);
+        let drone = app
+            .world
+            .spawn((
+                Viewable::Dynamic,
+                GlobalTransform::from_translation(position),
+            ))
+            .id();
+        let ghost = app
+            .world
+            .spawn((
+                Ghost {
+                    entity: drone,
+                    last_seen: 0.0,
+                },
+                GlobalTransform::from_translation(position.truncate().extend(GHOST_Z)),
+            ))
+            .id();
+
+        (app, drone, ghost)
+    }
+
+    #[test]
+    fn ghost_stays_while_its_entity_is_still_there() {
+        let (mut app, _, ghost) = setup();
+
+        app.update();
+        app.update();
+        assert!(app.world.get_entity(ghost).is_some());
+    }
+
+    #[test]
+    fn ghost_clears_once_its_spot_is_seen_empty() {
+        let (mut app, drone, ghost) = setup();
+
+        app.update();
+        *app.world.get_mut::<GlobalTransform>(drone).unwrap() =
+            GlobalTransform::from_xyz(TILE_SIZE * 4.0, TILE_SIZE * 20.0, 0.0);
+        app.update();
+        assert!(app.world.get_entity(ghost).is_none());
+    }
+}
@@ -0,0 +1,72 @@
+use bevy::prelude::*;
+
+/// How a collider affects sight passing through it
+///
+/// Colliders in `OPAQUE_GROUP` or `TRANSLUCENT_GROUP` without an `Occluder` block sight entirely.
+/// Whether or not an occluder blocks movement is up to its collider, e.g. a window is a solid
+/// collider whereas smoke is a `Sensor`.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct Occluder {
+    /// How much of the remaining view distance is kept after seeing through this occluder, from
+    /// `0.0` (fully opaque) to `1.0` (fully transparent)
+    pub transmittance: f32,
+    /// If set, sight only passes through travelling in this (local) direction; from the other
+    /// side, this occluder is opaque
+    pub one_way: Option<Vec2>,
+}
+
+impl Occluder {
+    /// Clear glass: blocks movement, but not sight
+    pub const WINDOW: Self = Self {
+        transmittance: 1.0,
+        one_way: None,
+    };
+
+    /// Something like smoke, that shortens the view of anything seen through it
+    pub fn new(transmittance: f32) -> Self {
+        Self {
+            transmittance: transmittance.clamp(0.0, 1.0),
+            one_way: None,
+        }
+    }
+
+    /// A one-way mirror, which can only be seen through looking in the (local) `direction`
+    pub fn one_way_mirror(direction: Vec2) -> Self {
+        Self {
+            transmittance: 1.0,
+            one_way: Some(direction),
+        }
+    }
+}
+
+/// Look up how much sight gets through an entity
+pub trait Transmittance {
+    /// How much of the remaining view distance is kept after a ray travelling in `direction` sees
+    /// through `entity`; `0.0` means it blocks sight entirely
+    fn transmittance(&self, entity: Entity, direction: Vec2) -> f32;
+}
+
+/// Without any occluders, everything blocks sight
+impl Transmittance for () {
+    fn transmittance(&self, _entity: Entity, _direction: Vec2) -> f32 {
+        0.0
+    }
+}
+
+impl<'w, 's, 'a, 'b> Transmittance for Query<'w, 's, (&'a Occluder, &'b GlobalTransform)> {
+    fn transmittance(&self, entity: Entity, direction: Vec2) -> f32 {
+        let Ok((occluder, transform)) = self.get(entity) else {
+            return 0.0;
+        };
+
+        if let Some(one_way) = occluder.one_way {
+            let one_way = transform.affine().transform_vector3(one_way.extend(0.0));
+            if one_way.truncate().dot(direction) < 0.0 {
+                // We're looking at it from the wrong side
+                return 0.0;
+            }
+        }
+
+        occluder.transmittance
+    }
+}
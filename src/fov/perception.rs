@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::{visibility, FieldOfView, Occluder, Viewable};
+use crate::lighting::LightMap;
+
+/// The `Viewable` entities a `FieldOfView` can currently see
+///
+/// This is updated every frame from line of sight and, if there is a `LightMap`, how well lit each
+/// entity is. It does not depend on any render targets; it is the component game logic (AI,
+/// stealth, etc.) should query to ask what a viewer can see.
+/// Not to be confused with Bevy's own render-side `VisibleEntities`, which belongs to cameras.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deref, Component)]
+pub struct VisibleEntities(Vec<Entity>);
+
+impl VisibleEntities {
+    /// Whether or not `entity` is currently seen
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.0.contains(&entity)
+    }
+}
+
+pub fn add_visible_entities(
+    mut commands: Commands,
+    viewer_qry: Query<Entity, (Added<FieldOfView>, Without<VisibleEntities>)>,
+) {
+    for viewer in viewer_qry.iter() {
+        commands.entity(viewer).insert(VisibleEntities::default());
+    }
+}
+
+pub fn update_visible_entities(
+    rapier_context: Res<RapierContext>,
+    mut viewer_qry: Query<(Entity, &GlobalTransform, &FieldOfView, &mut VisibleEntities)>,
+    viewable_qry: Query<(Entity, &GlobalTransform), With<Viewable>>,
+    occluder_qry: Query<(&Occluder, &GlobalTransform)>,
+    light_map: Option<Res<LightMap>>,
+) {
+    for (viewer, viewer_transform, viewer_fov, mut visible) in viewer_qry.iter_mut() {
+        let origin = viewer_transform.translation().truncate();
+        let view_direction = viewer_transform.right().truncate();
+
+        let seen = viewable_qry
+            .iter()
+            .filter(|&(viewable, _)| viewable != viewer)
+            .filter(|(_, viewable_transform)| {
+                // Things in the dark can only be seen up close
+                let position = viewable_transform.translation().truncate();
+                light_map
+                    .as_ref()
+                    .map(|light_map| {
+                        light_map.is_visible(position)
+                            || position.distance(origin) <= viewer_fov.dark_vision
+                    })
+                    .unwrap_or(true)
+            })
+            .filter(|(viewable, viewable_transform)| {
+                visibility::has_line_of_sight(
+                    viewer,
+                    origin,
+                    viewer_fov,
+                    view_direction,
+                    *viewable,
+                    viewable_transform.translation().truncate(),
+                    &rapier_context,
+                    &occluder_qry,
+                )
+            })
+            .map(|(viewable, _)| viewable)
+            .collect();
+
+        // Only trigger change detection if what we can see has actually changed
+        visible.set_if_neq(VisibleEntities(seen));
+    }
+}
@@ -7,6 +7,9 @@ pub enum Viewable {
     Static,
     /// Dynamic viewables are only visible while within line of sight, e.g. raging fungus monsters
     Dynamic,
+    /// Always drawn, but like any other viewable only perceived by viewers within line of sight,
+    /// e.g. the player
+    Always,
 }
 
 impl From<Viewable> for RenderLayers {
@@ -14,6 +17,7 @@ impl From<Viewable> for RenderLayers {
         match value {
             Viewable::Static => RenderLayers::layer(1),
             Viewable::Dynamic => RenderLayers::layer(2),
+            Viewable::Always => RenderLayers::default(),
         }
     }
 }
@@ -0,0 +1,904 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+use bevy_rapier2d::{
+    prelude::*,
+    rapier::{
+        math::{Isometry, Point, Real},
+        parry::shape::{Shape, TypedShape},
+    },
+};
+
+use crate::core::{OPAQUE_GROUP, TRANSLUCENT_GROUP};
+
+use super::{FieldOfView, Transmittance};
+
+/// How far either side of a corner to cast additional rays, to find what lies behind it
+const CORNER_EPSILON: f32 = 0.0001;
+
+/// How close together, in radians, the edges of two arcs must be to count as touching
+///
+/// Angles are wrapped and summed on their way here, so this is well above `f32::EPSILON`.
+const ARC_EPSILON: f32 = 1e-4;
+
+/// How many times to subdivide between a pair of rays during adaptive refinement
+const MAX_REFINE_DEPTH: u32 = 4;
+
+/// Filter for rays cast from `viewer` that should only be stopped by colliders that affect sight
+pub(super) fn view_filter<'a>(viewer: Entity) -> QueryFilter<'a> {
+    QueryFilter::new()
+        .groups(CollisionGroups::new(
+            Group::all(),
+            OPAQUE_GROUP | TRANSLUCENT_GROUP,
+        ))
+        .exclude_collider(viewer)
+}
+
+/// Cast a single ray of sight up to `distance`, seeing through any translucent occluders it meets
+///
+/// Each occluder seen through shortens the rest of the view according to its transmittance. Returns
+/// how far along the ray can be seen, and whether the ray ended because it was blocked (rather
+/// than simply running out of view distance).
+fn cast_sight_ray(
+    origin: Vec2,
+    direction: Vec2,
+    distance: f32,
+    filter: QueryFilter,
+    ignore: &[Entity],
+    rapier_context: &RapierContext,
+    occluders: &impl Transmittance,
+) -> (f32, bool) {
+    let solid = true;
+
+    let mut seen_through = ignore.to_vec();
+    let mut start = 0.0;
+    let mut reach = distance;
+    loop {
+        let not_seen_through = |entity| !seen_through.contains(&entity);
+        let filter = filter.predicate(&not_seen_through);
+
+        let Some((entity, toi)) = rapier_context.cast_ray(
+            origin + direction * start,
+            direction,
+            reach - start,
+            solid,
+            filter,
+        ) else {
+            return (reach, false);
+        };
+
+        let hit = start + toi;
+        let transmittance = occluders.transmittance(entity, direction);
+        if transmittance <= 0.0 {
+            return (hit, true);
+        }
+
+        // Everything beyond this occluder is seen through it
+        seen_through.push(entity);
+        reach = hit + (reach - hit) * transmittance;
+        start = hit;
+    }
+}
+
+/// Check if `target` is within `viewer`'s field of view and not blocked by anything
+#[allow(clippy::too_many_arguments)]
+pub(super) fn has_line_of_sight(
+    viewer: Entity,
+    origin: Vec2,
+    fov: &FieldOfView,
+    view_direction: Vec2,
+    target: Entity,
+    target_pos: Vec2,
+    rapier_context: &RapierContext,
+    occluders: &impl Transmittance,
+) -> bool {
+    let to_target = target_pos - origin;
+    let distance = to_target.length();
+
+    // How far we can see in the target's direction
+    let reach = fov
+        .cones()
+        .filter(|cone| cone.contains(view_direction, to_target))
+        .map(|cone| cone.distance)
+        .fold(0.0, f32::max);
+    if reach <= 0.0 {
+        return false;
+    }
+    if distance <= f32::EPSILON {
+        // We're right on top of it, no need to look any further
+        return true;
+    }
+
+    // The target may well have an opaque collider of its own; don't let it hide itself
+    let (seen, _) = cast_sight_ray(
+        origin,
+        to_target / distance,
+        reach,
+        view_filter(viewer),
+        &[target],
+        rapier_context,
+        occluders,
+    );
+
+    seen >= distance
+}
+
+/// Where a single ray of sight ended up
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RayHit {
+    /// Angle of the ray, relative to the start of its arc
+    angle: f32,
+    /// How far along the ray can be seen
+    toi: f32,
+    /// Whether the ray was stopped by something, rather than running out of view distance
+    blocked: bool,
+}
+
+/// A contiguous arc of a field of view with a single view distance
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ViewArc {
+    /// Angle the arc starts at, counter-clockwise from directly behind the viewer
+    from: f32,
+    span: f32,
+    distance: f32,
+    /// Direction of the start of the arc
+    start: Vec2,
+}
+
+impl ViewArc {
+    /// Direction of the ray `angle` radians counter-clockwise from the start of the arc
+    fn ray(&self, angle: f32) -> Vec2 {
+        Vec2::from_angle(angle).rotate(self.start)
+    }
+
+    /// Check if `next` picks up exactly where this arc leaves off
+    fn adjoins(&self, next: &ViewArc) -> bool {
+        (self.from + self.span - next.from).abs() <= ARC_EPSILON
+    }
+}
+
+/// Split the union of a view's cones into arcs, ordered counter-clockwise from directly behind
+///
+/// Where cones overlap, the longest view distance wins.
+fn view_arcs(fov: &FieldOfView, view_direction: Vec2) -> Vec<ViewArc> {
+    let behind = -view_direction;
+
+    // Each cone as (start, span, distance), measured from directly behind
+    let cones = fov
+        .cones()
+        .map(|cone| {
+            let span = (cone.half_angle * 2.0).min(TAU);
+            let start = (PI + cone.direction - cone.half_angle).rem_euclid(TAU);
+            (start, span, cone.distance)
+        })
+        .collect::<Vec<_>>();
+
+    let mut breaks = vec![0.0, TAU];
+    for &(start, span, _) in cones.iter() {
+        breaks.push(start);
+        breaks.push((start + span).rem_euclid(TAU));
+    }
+    breaks.sort_unstable_by(f32::total_cmp);
+    breaks.dedup_by(|a, b| (*a - *b).abs() <= ARC_EPSILON);
+
+    let mut arcs: Vec<ViewArc> = Vec::new();
+    for pair in breaks.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+        let middle = (from + to) / 2.0;
+        let distance = cones
+            .iter()
+            .filter(|&&(start, span, _)| (middle - start).rem_euclid(TAU) <= span)
+            .map(|&(_, _, distance)| distance)
+            .fold(0.0, f32::max);
+        if distance <= 0.0 {
+            continue;
+        }
+
+        match arcs.last_mut() {
+            // Extend the previous arc if this one just carries on from it
+            Some(last)
+                if last.distance == distance
+                    && (last.from + last.span - from).abs() <= ARC_EPSILON =>
+            {
+                last.span = to - last.from;
+            }
+            _ => arcs.push(ViewArc {
+                from,
+                span: to - from,
+                distance,
+                start: Vec2::from_angle(from).rotate(behind),
+            }),
+        }
+    }
+
+    arcs
+}
+
+/// Join the points cast for each arc into a single triangle fan around `origin`
+fn merge_arcs(
+    origin: Vec2,
+    arcs: &[ViewArc],
+    mut cast_arc: impl FnMut(&ViewArc) -> Vec<Vec2>,
+) -> Vec<Vec2> {
+    let mut points = vec![origin];
+    for (idx, arc) in arcs.iter().enumerate() {
+        // Where there's a gap between arcs, pull our edge back in to the viewer
+        if idx > 0 && !arcs[idx - 1].adjoins(arc) {
+            points.push(origin);
+        }
+        points.extend(cast_arc(arc));
+    }
+
+    points
+}
+
+/// Calculate the visibility polygon of `viewer`'s field of view
+///
+/// Rather than sweeping the view with a ray every degree, this only casts rays toward the corners of
+/// opaque colliders (and either side of them, to see past them) and toward the points where their
+/// edges cross the edge of the view, giving exact shadow edges with far fewer rays. Where
+/// neighbouring rays still hit at very different distances, more are cast between them according
+/// to the view's `refine_distance`.
+///
+/// The returned points are a triangle fan: the first point is `origin`, and the rest trace the
+/// edge of the view counter-clockwise; every cone in the view is merged into this single polygon.
+pub fn cast_view_cone(
+    viewer: Entity,
+    origin: Vec2,
+    fov: &FieldOfView,
+    view_direction: Vec2,
+    rapier_context: &RapierContext,
+    occluders: &impl Transmittance,
+) -> Vec<Vec2> {
+    let filter = view_filter(viewer);
+
+    // Gather the outlines of every collider that could be within view and affect it
+    let mut outlines = Outlines::default();
+    let scale = rapier_context.physics_scale();
+    rapier_context.intersections_with_shape(
+        origin,
+        0.0,
+        &Collider::ball(fov.max_distance()),
+        filter,
+        |entity| {
+            if let Some(collider) = rapier_context
+                .entity2collider()
+                .get(&entity)
+                .and_then(|&handle| rapier_context.colliders.get(handle))
+            {
+                outlines.add_shape(collider.shape(), collider.position(), scale);
+            }
+            true
+        },
+    );
+
+    let arcs = view_arcs(fov, view_direction);
+    merge_arcs(origin, &arcs, |arc| {
+        // Angles, relative to the start of our arc, that we need to cast rays toward
+        // Always include both edges of our arc
+        let mut angles = vec![0.0, arc.span];
+        for point in outlines.targets(origin, arc.distance) {
+            let angle = arc.start.angle_between(point - origin).rem_euclid(TAU);
+            for angle in [angle - CORNER_EPSILON, angle, angle + CORNER_EPSILON] {
+                if (0.0..=arc.span).contains(&angle) {
+                    angles.push(angle);
+                }
+            }
+        }
+        angles.sort_unstable_by(f32::total_cmp);
+        angles.dedup_by(|a, b| (*a - *b).abs() < CORNER_EPSILON / 2.0);
+
+        let cast = |angle: f32| {
+            let (toi, blocked) = cast_sight_ray(
+                origin,
+                arc.ray(angle),
+                arc.distance,
+                filter,
+                &[],
+                rapier_context,
+                occluders,
+            );
+            RayHit {
+                angle,
+                toi,
+                blocked,
+            }
+        };
+
+        // Anything our outlines missed, e.g. curved shapes, shows up as neighbouring rays hitting
+        // at very different distances
+        let mut hits: Vec<RayHit> = Vec::with_capacity(angles.len());
+        for angle in angles {
+            let hit = cast(angle);
+            if let (Some(refine_distance), Some(&previous)) = (fov.refine_distance, hits.last()) {
+                refine(
+                    previous,
+                    hit,
+                    refine_distance,
+                    MAX_REFINE_DEPTH,
+                    &cast,
+                    &mut hits,
+                );
+            }
+            hits.push(hit);
+        }
+
+        let step = fov.resolution.step(arc.distance);
+        let mut points = Vec::new();
+        let mut previous: Option<RayHit> = None;
+        for hit in hits {
+            if let Some(prev) = previous {
+                // Nothing can be hiding between two rays that both reach the edge of our view (we'd
+                // have cast a ray at it otherwise), so trace the arc between them without casting more
+                if !prev.blocked && !hit.blocked && (prev.toi - hit.toi).abs() <= f32::EPSILON {
+                    let steps = ((hit.angle - prev.angle) / step).ceil() as usize;
+                    points.extend((1..steps).map(|step| {
+                        let arc_angle =
+                            prev.angle + (hit.angle - prev.angle) * step as f32 / steps as f32;
+                        origin + arc.ray(arc_angle) * hit.toi
+                    }));
+                }
+            }
+            points.push(origin + arc.ray(hit.angle) * hit.toi);
+            previous = Some(hit);
+        }
+
+        points
+    })
+}
+
+/// Calculate the visibility polygon of `viewer`'s field of view by sweeping it with evenly spaced rays
+///
+/// Rays are spaced according to the view's `resolution`; where adjacent rays hit at very different
+/// distances, more rays are cast between them to better find the edge of whatever is in the way.
+///
+/// This is simpler, but less precise and casts many more rays than `cast_view_cone`; it is kept
+/// around as a baseline to compare against.
+pub fn cast_view_cone_fixed_step(
+    viewer: Entity,
+    origin: Vec2,
+    fov: &FieldOfView,
+    view_direction: Vec2,
+    rapier_context: &RapierContext,
+    occluders: &impl Transmittance,
+) -> Vec<Vec2> {
+    let filter = view_filter(viewer);
+
+    let arcs = view_arcs(fov, view_direction);
+    merge_arcs(origin, &arcs, |arc| {
+        // Evenly divide our arc so we land exactly on both of its edges
+        let num_rays = (arc.span / fov.resolution.step(arc.distance))
+            .ceil()
+            .max(1.0) as usize;
+
+        let cast = |angle: f32| {
+            let (toi, blocked) = cast_sight_ray(
+                origin,
+                arc.ray(angle),
+                arc.distance,
+                filter,
+                &[],
+                rapier_context,
+                occluders,
+            );
+            RayHit {
+                angle,
+                toi,
+                blocked,
+            }
+        };
+
+        // Iterate inclusively to ensure we include both edges of our arc
+        let mut hits = vec![cast(0.0)];
+        for step in 1..=num_rays {
+            let angle = arc.span * step as f32 / num_rays as f32;
+            let hit = cast(angle);
+            if let Some(refine_distance) = fov.refine_distance {
+                let previous = *hits.last().unwrap();
+                refine(
+                    previous,
+                    hit,
+                    refine_distance,
+                    MAX_REFINE_DEPTH,
+                    &cast,
+                    &mut hits,
+                );
+            }
+            hits.push(hit);
+        }
+
+        hits.into_iter()
+            .map(|hit| origin + arc.ray(hit.angle) * hit.toi)
+            .collect()
+    })
+}
+
+/// Recursively cast rays between two hits that differ too much in distance
+///
+/// Any new hits are pushed onto `hits` in order, between `from` (assumed to already be in `hits`)
+/// and `to` (assumed to be pushed after). Rays closer together than `CORNER_EPSILON` are never
+/// split any further.
+fn refine(
+    from: RayHit,
+    to: RayHit,
+    refine_distance: f32,
+    depth: u32,
+    cast: &impl Fn(f32) -> RayHit,
+    hits: &mut Vec<RayHit>,
+) {
+    if depth == 0
+        || (from.toi - to.toi).abs() <= refine_distance
+        || to.angle - from.angle <= CORNER_EPSILON
+    {
+        return;
+    }
+
+    let angle = (from.angle + to.angle) / 2.0;
+    let mid = cast(angle);
+    refine(from, mid, refine_distance, depth - 1, cast, hits);
+    hits.push(mid);
+    refine(mid, to, refine_distance, depth - 1, cast, hits);
+}
+
+/// The outlines of opaque colliders, in world space
+#[derive(Debug, Default)]
+struct Outlines {
+    corners: Vec<Vec2>,
+    edges: Vec<(Vec2, Vec2)>,
+    circles: Vec<(Vec2, f32)>,
+}
+
+impl Outlines {
+    fn add_polygon(&mut self, vertices: impl IntoIterator<Item = Vec2>, closed: bool) {
+        let start = self.corners.len();
+        self.corners.extend(vertices);
+
+        let vertices = &self.corners[start..];
+        self.edges
+            .extend(vertices.windows(2).map(|pair| (pair[0], pair[1])));
+        if closed && vertices.len() > 2 {
+            self.edges.push((vertices[vertices.len() - 1], vertices[0]));
+        }
+    }
+
+    fn add_shape(&mut self, shape: &dyn Shape, position: &Isometry<Real>, scale: f32) {
+        let to_world = |point: &Point<Real>| {
+            let point = position * point;
+            Vec2::new(point.x, point.y) * scale
+        };
+
+        match shape.as_typed_shape() {
+            TypedShape::Ball(ball) => {
+                self.circles
+                    .push((to_world(&Point::origin()), ball.radius * scale));
+            }
+            TypedShape::Capsule(capsule) => {
+                let (a, b) = (to_world(&capsule.segment.a), to_world(&capsule.segment.b));
+                let radius = capsule.radius * scale;
+                let normal = (b - a).perp().normalize_or_zero() * radius;
+                self.circles.extend([(a, radius), (b, radius)]);
+                self.add_polygon([a + normal, b + normal], false);
+                self.add_polygon([a - normal, b - normal], false);
+            }
+            TypedShape::Cuboid(cuboid) => {
+                let (x, y) = (cuboid.half_extents.x, cuboid.half_extents.y);
+                self.add_polygon(
+                    [
+                        Point::new(-x, -y),
+                        Point::new(x, -y),
+                        Point::new(x, y),
+                        Point::new(-x, y),
+                    ]
+                    .iter()
+                    .map(to_world),
+                    true,
+                );
+            }
+            TypedShape::Segment(segment) => {
+                self.add_polygon([to_world(&segment.a), to_world(&segment.b)], false);
+            }
+            TypedShape::Triangle(triangle) => {
+                self.add_polygon(triangle.vertices().iter().map(to_world), true);
+            }
+            TypedShape::ConvexPolygon(polygon) => {
+                self.add_polygon(polygon.points().iter().map(to_world), true);
+            }
+            TypedShape::Polyline(polyline) => {
+                self.corners
+                    .extend(polyline.vertices().iter().map(to_world));
+                self.edges.extend(
+                    polyline
+                        .segments()
+                        .map(|segment| (to_world(&segment.a), to_world(&segment.b))),
+                );
+            }
+            TypedShape::TriMesh(trimesh) => {
+                for triangle in trimesh.triangles() {
+                    self.add_polygon(triangle.vertices().iter().map(to_world), true);
+                }
+            }
+            TypedShape::Compound(compound) => {
+                for (sub_position, sub_shape) in compound.shapes() {
+                    self.add_shape(&**sub_shape, &(position * sub_position), scale);
+                }
+            }
+            _ => {
+                // We don't know this shape's outline, so fall back to the corners of its bounds
+                let aabb = shape.compute_aabb(position);
+                let (min, max) = (
+                    Vec2::new(aabb.mins.x, aabb.mins.y) * scale,
+                    Vec2::new(aabb.maxs.x, aabb.maxs.y) * scale,
+                );
+                self.add_polygon(
+                    [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)],
+                    true,
+                );
+            }
+        }
+    }
+
+    /// All the points within `range` of `origin` that a ray should be cast toward
+    ///
+    /// This is every corner within range, every point where an edge crosses the edge of the view,
+    /// and for circles the points their silhouettes' edges touch.
+    fn targets(&self, origin: Vec2, range: f32) -> Vec<Vec2> {
+        let mut targets = self
+            .corners
+            .iter()
+            .copied()
+            .filter(|corner| corner.distance_squared(origin) <= range * range)
+            .collect::<Vec<_>>();
+
+        for &(a, b) in self.edges.iter() {
+            targets.extend(segment_circle_intersections(a, b, origin, range));
+        }
+
+        for &(center, radius) in self.circles.iter() {
+            let to_center = center - origin;
+            let distance = to_center.length();
+            if distance <= radius {
+                // We're inside this circle, it doesn't cast a shadow we can find the edge of
+                continue;
+            }
+
+            // Tangent points
+            let offset = (radius / distance).asin();
+            let tangent_length = (distance * distance - radius * radius).sqrt();
+            for angle in [-offset, offset] {
+                let tangent = Vec2::from_angle(angle).rotate(to_center / distance);
+                if tangent_length <= range {
+                    targets.push(origin + tangent * tangent_length);
+                }
+            }
+
+            targets.extend(circle_intersections(center, radius, origin, range));
+        }
+
+        targets
+    }
+}
+
+/// Find the points where the segment from `a` to `b` crosses the circle
+fn segment_circle_intersections(a: Vec2, b: Vec2, center: Vec2, radius: f32) -> Vec<Vec2> {
+    let direction = b - a;
+    let offset = a - center;
+
+    let qa = direction.length_squared();
+    let qb = 2.0 * offset.dot(direction);
+    let qc = offset.length_squared() - radius * radius;
+    let discriminant = qb * qb - 4.0 * qa * qc;
+    if qa <= f32::EPSILON || discriminant < 0.0 {
+        return Vec::new();
+    }
+
+    let root = discriminant.sqrt();
+    [(-qb - root) / (2.0 * qa), (-qb + root) / (2.0 * qa)]
+        .into_iter()
+        .filter(|t| (0.0..=1.0).contains(t))
+        .map(|t| a + direction * t)
+        .collect()
+}
+
+/// Find the points where two circles cross each other
+fn circle_intersections(center_a: Vec2, radius_a: f32, center_b: Vec2, radius_b: f32) -> Vec<Vec2> {
+    let between = center_b - center_a;
+    let distance = between.length();
+    if distance <= f32::EPSILON
+        || distance > radius_a + radius_b
+        || distance < (radius_a - radius_b).abs()
+    {
+        return Vec::new();
+    }
+
+    let along =
+        (radius_a * radius_a - radius_b * radius_b + distance * distance) / (2.0 * distance);
+    let across = (radius_a * radius_a - along * along).max(0.0).sqrt();
+    let direction = between / distance;
+    let midpoint = center_a + direction * along;
+
+    vec![
+        midpoint + direction.perp() * across,
+        midpoint - direction.perp() * across,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+
+    use super::*;
+    use crate::{
+        fov::{Occluder, RayResolution, ViewCone},
+        map::TILE_SIZE,
+    };
+
+    /// Build a headless world with Rapier, and a collider in `group` for each of `colliders`
+    fn world(colliders: impl IntoIterator<Item = (Vec2, Collider, Group)>) -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            RapierPhysicsPlugin::<()>::pixels_per_meter(32.0),
+        ));
+        for (position, collider, group) in colliders {
+            app.world.spawn((
+                TransformBundle::from_transform(Transform::from_translation(position.extend(0.0))),
+                collider,
+                CollisionGroups::new(group, Group::all()),
+            ));
+        }
+
+        // Let Rapier pick up all our colliders
+        app.update();
+        app.update();
+
+        app
+    }
+
+    /// A box 20 wide, with its near face 90 to the right of the origin
+    fn box_ahead() -> App {
+        world([(
+            Vec2::new(100.0, 0.0),
+            Collider::cuboid(10.0, 10.0),
+            OPAQUE_GROUP,
+        )])
+    }
+
+    /// Angle of `point` counter-clockwise from the X axis
+    fn angle(point: Vec2) -> f32 {
+        point.y.atan2(point.x)
+    }
+
+    #[test]
+    fn a_box_casts_an_exact_shadow() {
+        let mut app = box_ahead();
+        let viewer = app.world.spawn_empty().id();
+        let fov = FieldOfView::new(200.0, PI);
+        let points = cast_view_cone(
+            viewer,
+            Vec2::ZERO,
+            &fov,
+            Vec2::X,
+            app.world.resource::<RapierContext>(),
+            &(),
+        );
+
+        // Rays were cast right up to the box's near corners...
+        for corner in [Vec2::new(90.0, 10.0), Vec2::new(90.0, -10.0)] {
+            assert!(
+                points.iter().any(|point| point.distance(corner) < 0.05),
+                "no point near {corner}"
+            );
+        }
+        // ...and just past them, to the edge of the view
+        let edge = angle(Vec2::new(90.0, 10.0));
+        assert!(points[1..]
+            .iter()
+            .any(|point| { point.length() > 199.9 && (angle(*point).abs() - edge).abs() < 0.001 }));
+        // Nothing within the shadow is seen past the box's near face
+        for point in points[1..].iter() {
+            if angle(*point).abs() < edge - 0.001 {
+                assert!(point.x <= 90.01, "{point} is behind the box");
+            }
+        }
+    }
+
+    /// A fake ray cast that sees `near` up to `edge` radians, then `far` beyond it
+    fn step_at(edge: f32, near: f32, far: f32) -> impl Fn(f32) -> RayHit {
+        move |angle| RayHit {
+            angle,
+            toi: if angle < edge { near } else { far },
+            blocked: angle < edge,
+        }
+    }
+
+    #[test]
+    fn refine_homes_in_on_a_jump_in_distance() {
+        let cast = step_at(0.3, 10.0, 100.0);
+        let mut hits = vec![cast(0.0)];
+        refine(cast(0.0), cast(1.0), 16.0, 4, &cast, &mut hits);
+        hits.push(cast(1.0));
+
+        // Rays were added in order, and narrowed down on the jump as far as our depth allows
+        assert!(hits.len() > 2);
+        assert!(hits.windows(2).all(|pair| pair[0].angle < pair[1].angle));
+        let jump = hits
+            .windows(2)
+            .find(|pair| pair[0].toi != pair[1].toi)
+            .unwrap();
+        assert!(jump[0].angle < 0.3 && jump[1].angle >= 0.3);
+        assert!(jump[1].angle - jump[0].angle <= 1.0 / 16.0);
+    }
+
+    #[test]
+    fn refine_leaves_similar_hits_alone() {
+        let cast = step_at(0.3, 10.0, 20.0);
+        let mut hits = Vec::new();
+        refine(cast(0.0), cast(1.0), 16.0, 4, &cast, &mut hits);
+        assert!(hits.is_empty());
+
+        // Nor does it go any deeper than asked
+        let cast = step_at(0.3, 10.0, 100.0);
+        refine(cast(0.0), cast(1.0), 16.0, 0, &cast, &mut hits);
+        assert!(hits.is_empty());
+    }
+
+    #[test]
+    fn fixed_step_refines_rays_at_shadow_edges() {
+        let mut app = box_ahead();
+        let viewer = app.world.spawn_empty().id();
+        let edge = angle(Vec2::new(90.0, 10.0));
+        let fov = FieldOfView::new(200.0, PI).with_resolution(RayResolution::Angle(0.2));
+
+        // How close any ray came to the edge of the box's shadow
+        let closest_to_edge = |fov: &FieldOfView| {
+            let points = cast_view_cone_fixed_step(
+                viewer,
+                Vec2::ZERO,
+                fov,
+                Vec2::X,
+                app.world.resource::<RapierContext>(),
+                &(),
+            );
+            let closest = points[1..]
+                .iter()
+                .map(|&point| (angle(point) - edge).abs())
+                .fold(f32::INFINITY, f32::min);
+            (points.len(), closest)
+        };
+
+        let (coarse_len, coarse) = closest_to_edge(&fov.clone().with_refine_distance(None));
+        let (refined_len, refined) = closest_to_edge(&fov.with_refine_distance(Some(TILE_SIZE)));
+        assert!(refined_len > coarse_len);
+        assert!(coarse > 0.05, "{coarse}");
+        assert!(refined < 0.02, "{refined}");
+    }
+
+    /// Check that `arc` covers `from..to` (measured from directly behind) out to `distance`
+    fn assert_arc(arc: &ViewArc, from: f32, to: f32, distance: f32) {
+        assert!(
+            (arc.from - from).abs() < ARC_EPSILON && (arc.from + arc.span - to).abs() < ARC_EPSILON,
+            "{arc:?} doesn't cover {from}..{to}"
+        );
+        assert_eq!(arc.distance, distance);
+    }
+
+    /// Cast a single point at the start of each arc, to see how they're joined together
+    fn arc_starts(arcs: &[ViewArc]) -> Vec<Vec2> {
+        merge_arcs(Vec2::ZERO, arcs, |arc| vec![arc.start * arc.distance])
+    }
+
+    #[test]
+    fn overlapping_cones_are_joined_into_their_union() {
+        let fov = FieldOfView::new(100.0, PI / 4.0).with_cone(ViewCone::new(50.0, PI / 2.0));
+        let arcs = view_arcs(&fov, Vec2::X);
+
+        // The longer view wins where they overlap, with the wider one either side
+        assert_eq!(arcs.len(), 3);
+        assert_arc(&arcs[0], PI / 2.0, PI * 3.0 / 4.0, 50.0);
+        assert_arc(&arcs[1], PI * 3.0 / 4.0, PI * 5.0 / 4.0, 100.0);
+        assert_arc(&arcs[2], PI * 5.0 / 4.0, PI * 3.0 / 2.0, 50.0);
+        assert!(arcs[1].start.abs_diff_eq(Vec2::from_angle(-PI / 4.0), 1e-4));
+
+        // Adjoining arcs are joined without returning to the viewer
+        let points = arc_starts(&arcs);
+        assert_eq!(points.len(), 4);
+        assert!(points[1..].iter().all(|&point| point != Vec2::ZERO));
+    }
+
+    #[test]
+    fn cones_wrap_around_behind_the_viewer() {
+        let fov =
+            FieldOfView::new(100.0, PI / 4.0).with_cone(ViewCone::new(50.0, PI / 4.0).facing(PI));
+        let arcs = view_arcs(&fov, Vec2::X);
+
+        // The rear cone is split either side of directly behind
+        assert_eq!(arcs.len(), 3);
+        assert_arc(&arcs[0], 0.0, PI / 4.0, 50.0);
+        assert_arc(&arcs[1], PI * 3.0 / 4.0, PI * 5.0 / 4.0, 100.0);
+        assert_arc(&arcs[2], PI * 7.0 / 4.0, TAU, 50.0);
+        assert!(arcs[0].start.abs_diff_eq(Vec2::NEG_X, 1e-4));
+
+        // Each gap between arcs returns to the viewer
+        let points = arc_starts(&arcs);
+        assert_eq!(points.len(), 6);
+        assert_eq!(points[2], Vec2::ZERO);
+        assert_eq!(points[4], Vec2::ZERO);
+
+        // A view all the way around is a single arc, starting and ending directly behind
+        let arcs = view_arcs(&FieldOfView::new(100.0, PI), Vec2::X);
+        assert_eq!(arcs.len(), 1);
+        assert_arc(&arcs[0], 0.0, TAU, 100.0);
+    }
+
+    /// What `cast_sight_ray` needs from the world to see through occluders
+    type SightParams = (
+        Res<'static, RapierContext>,
+        Query<'static, 'static, (&'static Occluder, &'static GlobalTransform)>,
+    );
+
+    #[test]
+    fn sight_carries_on_through_translucent_occluders() {
+        let mut app = world([(
+            Vec2::new(150.0, 0.0),
+            Collider::cuboid(1.0, 20.0),
+            OPAQUE_GROUP,
+        )]);
+        let wall = Collider::cuboid(1.0, 20.0);
+        app.world.spawn((
+            TransformBundle::from_transform(Transform::from_xyz(50.0, 0.0, 0.0)),
+            wall.clone(),
+            CollisionGroups::new(TRANSLUCENT_GROUP, Group::all()),
+            Occluder::WINDOW,
+        ));
+        app.world.spawn((
+            TransformBundle::from_transform(Transform::from_xyz(50.0, 100.0, 0.0)),
+            Collider::cuboid(10.0, 20.0),
+            Sensor,
+            CollisionGroups::new(TRANSLUCENT_GROUP, Group::all()),
+            Occluder::new(0.5),
+        ));
+        // Flipped around, so the mirror's local direction points left
+        app.world.spawn((
+            TransformBundle::from_transform(
+                Transform::from_xyz(50.0, 200.0, 0.0).with_rotation(Quat::from_rotation_z(PI)),
+            ),
+            wall,
+            CollisionGroups::new(TRANSLUCENT_GROUP, Group::all()),
+            Occluder::one_way_mirror(Vec2::NEG_X),
+        ));
+        app.update();
+        app.update();
+
+        let viewer = app.world.spawn_empty().id();
+        let mut state: SystemState<SightParams> = SystemState::new(&mut app.world);
+        let (rapier_context, occluders) = state.get(&app.world);
+        let sees = |origin: Vec2, direction: Vec2, distance: f32, blocked: bool| {
+            let seen = cast_sight_ray(
+                origin,
+                direction,
+                200.0,
+                view_filter(viewer),
+                &[],
+                &rapier_context,
+                &occluders,
+            );
+            // Rapier's hits aren't quite exact
+            assert!(
+                (seen.0 - distance).abs() < 0.05 && seen.1 == blocked,
+                "saw {seen:?} from {origin}, not {:?}",
+                (distance, blocked)
+            );
+        };
+
+        // Straight through the window, up to the wall behind it
+        sees(Vec2::ZERO, Vec2::X, 149.0, true);
+        // Smoke halves the rest of the view, from where we first meet it
+        sees(Vec2::new(0.0, 100.0), Vec2::X, 40.0 + 160.0 * 0.5, false);
+        // The mirror can be seen through from its left, once rotated into place...
+        sees(Vec2::new(0.0, 200.0), Vec2::X, 200.0, false);
+        // ...but blocks sight from its right
+        sees(Vec2::new(100.0, 200.0), Vec2::NEG_X, 49.0, true);
+    }
+}
@@ -0,0 +1,463 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    door::{Door, DoorRooms, DOOR_WIDTH},
+    fov::ExploredMap,
+    health::{Damage, DamageKind, Health, TakeDamage},
+    map::{tile_at, tile_center, Rooms, ShipParameters, TileGrid, TILE_SIZE},
+    rand::*,
+    ship_systems::{room_volume, HullBreach, ShipSystems},
+};
+
+/// How often hazards spread, burn and decay, and the ship's air moves, in seconds
+const HAZARD_TICK: f32 = 0.25;
+
+/// How likely each room is to start out with each kind of hazard
+const ROOM_FIRE_CHANCE: f64 = 0.1;
+const ROOM_RADIATION_CHANCE: f64 = 0.1;
+const ROOM_ELECTRIFIED_CHANCE: f64 = 0.05;
+
+/// How much a fire grows each tick while it has enough oxygen to burn
+const FIRE_GROWTH: f32 = 0.1;
+/// How much a fire dies down each tick once it's run out of oxygen
+const FIRE_DECAY: f32 = 0.2;
+/// Fires need at least this much oxygen in their room to keep burning
+const FIRE_MIN_OXYGEN: f32 = 0.2;
+/// How much oxygen a fully burning tile uses up each tick, in tiles of air
+const FIRE_OXYGEN_USE: f32 = 0.05;
+/// How likely a fully burning tile is to set each of its neighbours alight each tick
+const FIRE_SPREAD_CHANCE: f64 = 0.15;
+/// How hot a newly lit fire burns
+const FIRE_IGNITION: f32 = 0.2;
+
+/// How much of a tile's radiation leaks into each of its neighbours each tick
+const RADIATION_LEAK: f32 = 0.05;
+/// How much radiation fades away each tick
+const RADIATION_DECAY: f32 = 0.002;
+/// How radioactive the source of radiation in a room is
+const RADIATION_SOURCE: f32 = 1.0;
+
+/// How much damage each hazard does, per second, at full intensity
+const FIRE_DAMAGE: f32 = 20.0;
+const RADIATION_DAMAGE: f32 = 4.0;
+const ELECTRIFIED_DAMAGE: f32 = 10.0;
+
+/// The hazards present on a single tile
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HazardTile {
+    /// How fiercely the tile is burning, from `0.0` (not at all) to `1.0` (an inferno)
+    pub fire: f32,
+    /// How radioactive the tile is, from `0.0` (not at all) to `1.0` (lethal)
+    pub radiation: f32,
+    /// Whether the floor is electrified; it's only dangerous while its room has power
+    pub electrified: bool,
+}
+
+/// Hazards on every tile of the ship
+///
+/// Hazards change in discrete ticks, with any randomness seeded from the ship's seed and the tick,
+/// so that the same ship always plays out the same way given the same doors being opened.
+#[derive(Debug, Default, Clone, PartialEq, Resource, Serialize, Deserialize)]
+pub struct HazardMap {
+    tiles: TileGrid<HazardTile>,
+    /// How many ticks have passed
+    tick: u64,
+    /// Time since the last tick, in seconds
+    #[serde(default)]
+    since_tick: f32,
+}
+
+impl HazardMap {
+    /// Create a new map covering `bounds`, without any hazards
+    pub fn new(bounds: IRect) -> Self {
+        Self {
+            tiles: TileGrid::new(bounds, HazardTile::default()),
+            tick: 0,
+            since_tick: 0.0,
+        }
+    }
+
+    /// Cover `rooms` in hazards, which ones depending only on `seed`
+    pub fn generate(rooms: &Rooms, seed: u64) -> Self {
+        let mut hazards = Self::new(rooms.bounds());
+        let mut rng = seed_rng((seed, "hazards"));
+
+        for room in rooms.iter() {
+            if room.is_empty() {
+                continue;
+            }
+            let random_tile = |rng: &mut WyRand| {
+                IVec2::new(
+                    rng.gen_range(room.min.x..room.max.x),
+                    rng.gen_range(room.min.y..room.max.y),
+                )
+            };
+
+            if rng.gen_bool(ROOM_FIRE_CHANCE) {
+                hazards.ignite(random_tile(&mut rng), FIRE_IGNITION);
+            }
+            if rng.gen_bool(ROOM_RADIATION_CHANCE) {
+                if let Some(tile) = hazards.get_mut(random_tile(&mut rng)) {
+                    tile.radiation = RADIATION_SOURCE;
+                }
+            }
+            if rng.gen_bool(ROOM_ELECTRIFIED_CHANCE) {
+                // Electrify a strip of floor running across the room
+                let y = rng.gen_range(room.min.y..room.max.y);
+                for x in room.min.x..room.max.x {
+                    if let Some(tile) = hazards.get_mut(IVec2::new(x, y)) {
+                        tile.electrified = true;
+                    }
+                }
+            }
+        }
+
+        hazards
+    }
+
+    /// The tiles covered by this map
+    pub fn bounds(&self) -> IRect {
+        self.tiles.bounds()
+    }
+
+    /// How many ticks have passed
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Count `dt` seconds toward the next tick, returning how many ticks are now due
+    pub fn ticks_due(&mut self, dt: f32) -> u32 {
+        self.since_tick += dt.max(0.0);
+        let due = (self.since_tick / HAZARD_TICK).floor();
+        self.since_tick -= due * HAZARD_TICK;
+        due as u32
+    }
+
+    /// The hazards on `tile`; tiles outside of the map's bounds are always safe
+    pub fn get(&self, tile: IVec2) -> HazardTile {
+        self.tiles.get(tile).copied().unwrap_or_default()
+    }
+
+    pub fn get_mut(&mut self, tile: IVec2) -> Option<&mut HazardTile> {
+        self.tiles.get_mut(tile)
+    }
+
+    /// Set `tile` alight, if it isn't already burning hotter
+    pub fn ignite(&mut self, tile: IVec2, intensity: f32) {
+        if let Some(hazards) = self.get_mut(tile) {
+            hazards.fire = hazards.fire.max(intensity.clamp(0.0, 1.0));
+        }
+    }
+
+    /// Every tile with any hazard on it
+    pub fn iter(&self) -> impl Iterator<Item = (IVec2, &HazardTile)> {
+        self.tiles
+            .iter()
+            .filter(|(_, hazards)| **hazards != HazardTile::default())
+    }
+
+    /// Spread fire and radiation across the ship by a single tick
+    ///
+    /// `open_doors` are where each open door is, which hazards can spread through, and `oxygen` is
+    /// how much oxygen each room has, which fires need to burn and use up as they do. Any room
+    /// missing from `oxygen` has plenty, and never runs out. The outcome depends only on these and
+    /// `seed`, so the same ship always burns the same way.
+    pub fn step(&mut self, rooms: &Rooms, open_doors: &[Vec2], oxygen: &mut [f32], seed: u64) {
+        let mut rng = seed_rng((seed, "hazards", self.tick));
+        let mut next = self.tiles.clone();
+
+        // Fires burn through their room's oxygen, and die down once there's not enough left
+        let mut oxygen_used = vec![0.0; rooms.len()];
+        for (idx, (tile, hazard)) in self.tiles.iter().enumerate() {
+            if hazard.fire <= 0.0 {
+                continue;
+            }
+            let Some(room) = rooms.room_at(tile) else {
+                next[idx].fire = 0.0;
+                continue;
+            };
+
+            let room_oxygen = oxygen.get(room).copied().unwrap_or(1.0);
+            if room_oxygen >= FIRE_MIN_OXYGEN {
+                next[idx].fire = (hazard.fire + FIRE_GROWTH).min(1.0);
+                oxygen_used[room] += hazard.fire * FIRE_OXYGEN_USE;
+            } else {
+                next[idx].fire = (hazard.fire - FIRE_DECAY).max(0.0);
+                continue;
+            }
+
+            for direction in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                let neighbour = tile + direction;
+                // Roll first, so where fire can't spread doesn't change the rest of the rolls
+                let spreads = rng.gen_bool(FIRE_SPREAD_CHANCE * hazard.fire as f64);
+                let Some(neighbour_idx) = self.tiles.index(neighbour) else {
+                    continue;
+                };
+                if spreads
+                    && self.tiles[neighbour_idx].fire <= 0.0
+                    && can_spread(rooms, open_doors, tile, neighbour)
+                {
+                    next[neighbour_idx].fire = next[neighbour_idx].fire.max(FIRE_IGNITION);
+                }
+            }
+        }
+
+        // Radiation leaks into neighbouring tiles, fading as it goes
+        for (idx, (tile, hazard)) in self.tiles.iter().enumerate() {
+            if hazard.radiation <= 0.0 {
+                continue;
+            }
+            next[idx].radiation = (next[idx].radiation - RADIATION_DECAY).max(0.0);
+
+            for direction in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                let neighbour = tile + direction;
+                let Some(neighbour_idx) = self.tiles.index(neighbour) else {
+                    continue;
+                };
+                if can_spread(rooms, open_doors, tile, neighbour) {
+                    let leaked = hazard.radiation * RADIATION_LEAK;
+                    next[neighbour_idx].radiation = next[neighbour_idx].radiation.max(leaked);
+                }
+            }
+        }
+
+        for (idx, used) in oxygen_used.into_iter().enumerate() {
+            if let Some(oxygen) = oxygen.get_mut(idx).filter(|_| used > 0.0) {
+                *oxygen = (*oxygen - used / room_volume(rooms, idx)).max(0.0);
+            }
+        }
+
+        self.tiles = next;
+        self.tick += 1;
+    }
+}
+
+/// Check if hazards can spread from `from` to the neighbouring tile `to`
+///
+/// Hazards can't spread outside the ship, nor between rooms except through an open door.
+fn can_spread(rooms: &Rooms, open_doors: &[Vec2], from: IVec2, to: IVec2) -> bool {
+    let (Some(from_room), Some(to_room)) = (rooms.room_at(from), rooms.room_at(to)) else {
+        return false;
+    };
+    if from_room == to_room {
+        return true;
+    }
+
+    // Between rooms, only through a doorway with its door open
+    let crossing = (tile_center(from) + tile_center(to)) / 2.0;
+    open_doors
+        .iter()
+        .any(|door| door.distance(crossing) < DOOR_WIDTH / 2.0)
+}
+
+/// Cover a newly generated ship in hazards
+pub fn setup_hazards(mut commands: Commands, rooms: Res<Rooms>, ship: Res<ShipParameters>) {
+    commands.insert_resource(HazardMap::generate(&rooms, ship.seed.unwrap_or_default()));
+}
+
+/// Spread fire and radiation across the ship, and move its air around, a fixed tick at a time
+///
+/// Hazards and air affect each other, e.g. fires burn through oxygen, so both step on the same
+/// tick; however slow or fast frames are, the outcome is the same.
+#[allow(clippy::too_many_arguments)]
+pub fn simulate_hazards(
+    time: Res<Time>,
+    rooms: Res<Rooms>,
+    ship: Res<ShipParameters>,
+    door_qry: Query<(&Door, Option<&DoorRooms>, &GlobalTransform)>,
+    breach_qry: Query<(&HullBreach, &GlobalTransform)>,
+    mut ship_systems: Option<ResMut<ShipSystems>>,
+    mut hazards: ResMut<HazardMap>,
+) {
+    // Don't count waiting for a tick as the map changing
+    let ticks = hazards
+        .bypass_change_detection()
+        .ticks_due(time.delta_seconds());
+    if ticks == 0 {
+        return;
+    }
+
+    let open_doors = door_qry
+        .iter()
+        .filter(|(door, ..)| door.is_passable())
+        .map(|(_, rooms, transform)| (rooms.copied(), transform.translation().truncate()))
+        .collect::<Vec<_>>();
+    let door_positions = open_doors
+        .iter()
+        .map(|&(_, position)| position)
+        .collect::<Vec<_>>();
+    let air_doors = open_doors
+        .iter()
+        .filter_map(|&(rooms, position)| Some((rooms?, position)))
+        .collect::<Vec<_>>();
+    let breaches = breach_qry
+        .iter()
+        .map(|(&breach, transform)| (breach, transform.translation().truncate()))
+        .collect::<Vec<_>>();
+    let seed = ship.seed.unwrap_or_default();
+
+    // Catch up on every tick we've missed, so slow frames don't change the outcome
+    for _ in 0..ticks {
+        let mut oxygen = Vec::new();
+        if let Some(systems) = ship_systems.as_mut() {
+            systems.step_atmosphere(&rooms, &air_doors, &breaches, HAZARD_TICK);
+            oxygen = (0..rooms.len())
+                .map_while(|idx| systems.room(idx).map(|room| room.oxygen))
+                .collect();
+        }
+
+        hazards.step(&rooms, &door_positions, &mut oxygen, seed);
+
+        if let Some(systems) = ship_systems.as_mut() {
+            for (idx, &oxygen) in oxygen.iter().enumerate() {
+                if let Some(room) = systems.room_mut(idx) {
+                    room.oxygen = oxygen;
+                }
+            }
+        }
+    }
+}
+
+/// Hurt anything standing in a hazard
+pub fn hazard_damage(
+    time: Res<Time>,
+    rooms: Res<Rooms>,
+    hazards: Res<HazardMap>,
+    ship_systems: Option<Res<ShipSystems>>,
+    victim_qry: Query<(Entity, &GlobalTransform), With<Health>>,
+    mut damages: EventWriter<TakeDamage>,
+) {
+    let dt = time.delta_seconds();
+    if dt <= 0.0 {
+        return;
+    }
+
+    for (victim, transform) in victim_qry.iter() {
+        let tile = tile_at(transform.translation().truncate());
+        let hazard = hazards.get(tile);
+        let powered = rooms
+            .room_at(tile)
+            .and_then(|idx| ship_systems.as_ref()?.room(idx))
+            .map(|room| room.powered)
+            .unwrap_or(true);
+
+        let damage = [
+            (hazard.fire * FIRE_DAMAGE, DamageKind::Fire),
+            (hazard.radiation * RADIATION_DAMAGE, DamageKind::Radiation),
+            (
+                if hazard.electrified && powered {
+                    ELECTRIFIED_DAMAGE
+                } else {
+                    0.0
+                },
+                DamageKind::Energy,
+            ),
+        ];
+        for (amount, kind) in damage.into_iter().filter(|(amount, _)| *amount > 0.0) {
+            damages.send(TakeDamage {
+                target: victim,
+                source: None,
+                damage: Damage::new(amount * dt, kind),
+            });
+        }
+    }
+}
+
+/// Show hazards on tiles that have been explored
+pub fn draw_hazards(hazards: Res<HazardMap>, explored: Res<ExploredMap>, mut gizmos: Gizmos) {
+    let size = Vec2::splat(TILE_SIZE - 2.0);
+    for (tile, hazard) in hazards.iter() {
+        if !explored.is_explored(tile) {
+            continue;
+        }
+
+        let center = tile_center(tile);
+        if hazard.fire > 0.0 {
+            gizmos.rect_2d(center, 0.0, size, Color::ORANGE_RED.with_a(hazard.fire));
+        }
+        if hazard.radiation > 0.0 {
+            let color = Color::LIME_GREEN.with_a(hazard.radiation.min(1.0));
+            gizmos.circle_2d(center, TILE_SIZE / 4.0, color);
+        }
+        if hazard.electrified {
+            gizmos.line_2d(
+                center - size / 2.0,
+                center + size / 2.0,
+                Color::CYAN.with_a(0.6),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The open door between our two rooms
+    const DOOR: Vec2 = Vec2::new(4.0 * TILE_SIZE, 2.0 * TILE_SIZE);
+
+    /// Two 4×4 rooms side by side
+    fn rooms() -> Rooms {
+        Rooms::new(vec![IRect::new(0, 0, 4, 4), IRect::new(4, 0, 8, 4)])
+    }
+
+    fn burning(hazards: &HazardMap) -> Vec<IVec2> {
+        hazards
+            .iter()
+            .filter(|(_, hazard)| hazard.fire > 0.0)
+            .map(|(tile, _)| tile)
+            .collect()
+    }
+
+    #[test]
+    fn the_same_seed_plays_out_the_same() {
+        let rooms = rooms();
+        let run = |seed| {
+            let mut hazards = HazardMap::generate(&rooms, seed);
+            hazards.ignite(IVec2::new(1, 1), 1.0);
+            hazards.get_mut(IVec2::new(6, 2)).unwrap().radiation = RADIATION_SOURCE;
+            let mut oxygen = vec![1.0; rooms.len()];
+            for _ in 0..40 {
+                hazards.step(&rooms, &[DOOR], &mut oxygen, seed);
+            }
+            (hazards, oxygen)
+        };
+
+        let (hazards, oxygen) = run(42);
+        assert!(burning(&hazards).len() > 1);
+        assert_eq!(hazards.tick(), 40);
+        assert_eq!((hazards, oxygen), run(42));
+    }
+
+    #[test]
+    fn closed_doors_keep_hazards_in() {
+        let rooms = rooms();
+        let mut hazards = HazardMap::new(rooms.bounds());
+        for y in 0..4 {
+            for x in 0..4 {
+                hazards.ignite(IVec2::new(x, y), 1.0);
+            }
+        }
+        hazards.get_mut(IVec2::new(3, 2)).unwrap().radiation = RADIATION_SOURCE;
+
+        let mut oxygen = vec![1.0; rooms.len()];
+        for _ in 0..20 {
+            hazards.step(&rooms, &[], &mut oxygen, 0);
+        }
+
+        assert!(hazards.iter().all(|(tile, _)| tile.x < 4));
+        // Burning used up the air in the first room, but not the second
+        assert!(oxygen[0] < 1.0);
+        assert_eq!(oxygen[1], 1.0);
+    }
+
+    #[test]
+    fn ticks_carry_over_between_frames() {
+        let mut hazards = HazardMap::default();
+
+        assert_eq!(hazards.ticks_due(HAZARD_TICK * 0.4), 0);
+        assert_eq!(hazards.ticks_due(HAZARD_TICK * 2.4), 2);
+        assert_eq!(hazards.ticks_due(HAZARD_TICK * 0.4), 1);
+    }
+}
@@ -0,0 +1,321 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::Weapon,
+    fov::FieldOfView,
+    hearing::Hearing,
+    interaction::Interactable,
+    locomotion::{Locomotion, MoveIntent},
+    pathfinding::Waypoints,
+};
+
+/// How corpses are tinted, so they're obviously not going anywhere
+const CORPSE_TINT: Color = Color::rgb(0.4, 0.4, 0.4);
+
+/// The different ways something can be hurt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DamageKind {
+    /// Bullets, blades, blunt objects, etc.
+    Kinetic,
+    /// Lasers, plasma, electricity, etc.
+    Energy,
+    Fire,
+    Radiation,
+    /// Exposure to the vacuum of space
+    Vacuum,
+}
+
+/// An amount of damage of a particular kind
+///
+/// As a component, this is the damage something deals, e.g. a projectile or a hazard.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct Damage {
+    pub amount: f32,
+    pub kind: DamageKind,
+}
+
+impl Damage {
+    pub fn new(amount: f32, kind: DamageKind) -> Self {
+        Self { amount, kind }
+    }
+}
+
+/// How much something can be hurt before it dies
+#[derive(Debug, Clone, Copy, PartialEq, Component, Serialize, Deserialize)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    /// Create health that starts at its maximum
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    /// How much health is left, from `0.0` (dead) to `1.0` (unhurt)
+    pub fn fraction(&self) -> f32 {
+        if self.max > 0.0 {
+            (self.current / self.max).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    /// Heal up to, but not past, maximum health
+    pub fn heal(&mut self, amount: f32) {
+        self.current = (self.current + amount).min(self.max);
+    }
+}
+
+/// How much of each kind of damage is shrugged off, from `0.0` (none of it) to `1.0` (all of it)
+///
+/// Anything without resistances takes damage in full.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Component)]
+pub struct Resistances {
+    pub kinetic: f32,
+    pub energy: f32,
+    pub fire: f32,
+    pub radiation: f32,
+    pub vacuum: f32,
+}
+
+impl Resistances {
+    pub fn get(&self, kind: DamageKind) -> f32 {
+        match kind {
+            DamageKind::Kinetic => self.kinetic,
+            DamageKind::Energy => self.energy,
+            DamageKind::Fire => self.fire,
+            DamageKind::Radiation => self.radiation,
+            DamageKind::Vacuum => self.vacuum,
+        }
+    }
+
+    /// How much damage actually gets through these resistances
+    pub fn reduce(&self, damage: Damage) -> f32 {
+        damage.amount * (1.0 - self.get(damage.kind).clamp(0.0, 1.0))
+    }
+}
+
+/// What becomes of something when it dies
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Component)]
+pub enum OnDeath {
+    /// It's removed from the world entirely
+    #[default]
+    Despawn,
+    /// It stays where it fell, but no longer moves, sees, hears, shoots or can be hurt
+    LeaveCorpse,
+}
+
+/// Tag component for the remains of something that has died
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
+pub struct Corpse;
+
+/// Send to hurt something
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub struct TakeDamage {
+    pub target: Entity,
+    /// Whoever, or whatever, dealt the damage, if anyone
+    pub source: Option<Entity>,
+    pub damage: Damage,
+}
+
+/// Sent whenever something dies, e.g. for UI and AI to react to
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub struct Died {
+    pub entity: Entity,
+    /// Whoever, or whatever, dealt the killing blow, if anyone
+    pub killer: Option<Entity>,
+    /// Where it died
+    pub position: Vec2,
+}
+
+/// Hurt everything that's been damaged, and find out what's died
+pub fn apply_damage(
+    mut damages: EventReader<TakeDamage>,
+    mut health_qry: Query<(&mut Health, Option<&Resistances>, Option<&GlobalTransform>)>,
+    mut deaths: EventWriter<Died>,
+) {
+    for hit in damages.read() {
+        let Ok((mut health, resistances, transform)) = health_qry.get_mut(hit.target) else {
+            continue;
+        };
+        if health.is_dead() {
+            // Already dead, and dying again won't make it any more so
+            continue;
+        }
+
+        let amount = resistances
+            .map(|resistances| resistances.reduce(hit.damage))
+            .unwrap_or(hit.damage.amount);
+        health.current -= amount;
+
+        if health.is_dead() {
+            deaths.send(Died {
+                entity: hit.target,
+                killer: hit.source,
+                position: transform
+                    .map(|transform| transform.translation().truncate())
+                    .unwrap_or_default(),
+            });
+        }
+    }
+}
+
+/// Despawn whatever has died, or leave its corpse behind
+pub fn handle_deaths(
+    mut commands: Commands,
+    mut deaths: EventReader<Died>,
+    mut dead_qry: Query<(Option<&OnDeath>, Option<&mut Sprite>)>,
+) {
+    for death in deaths.read() {
+        let Ok((on_death, sprite)) = dead_qry.get_mut(death.entity) else {
+            continue;
+        };
+
+        match on_death.copied().unwrap_or_default() {
+            OnDeath::Despawn => commands.entity(death.entity).despawn_recursive(),
+            OnDeath::LeaveCorpse => {
+                commands
+                    .entity(death.entity)
+                    .remove::<(
+                        Health,
+                        Locomotion,
+                        MoveIntent,
+                        Waypoints,
+                        FieldOfView,
+                        Hearing,
+                        Interactable,
+                        Weapon,
+                    )>()
+                    .insert(Corpse);
+                if let Some(mut sprite) = sprite {
+                    sprite.color = CORPSE_TINT;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<TakeDamage>()
+            .add_event::<Died>()
+            .add_systems(Update, (apply_damage, handle_deaths.after(apply_damage)));
+        app
+    }
+
+    fn hurt(app: &mut App, target: Entity, source: Option<Entity>, damage: Damage) {
+        app.world.send_event(TakeDamage {
+            target,
+            source,
+            damage,
+        });
+    }
+
+    fn deaths(app: &App) -> Vec<Died> {
+        let events = app.world.resource::<Events<Died>>();
+        events.get_reader().read(events).copied().collect()
+    }
+
+    #[test]
+    fn resistances_reduce_damage() {
+        let mut app = setup();
+        let target = app
+            .world
+            .spawn((
+                Health::new(10.0),
+                Resistances {
+                    kinetic: 0.5,
+                    ..Default::default()
+                },
+            ))
+            .id();
+
+        hurt(
+            &mut app,
+            target,
+            None,
+            Damage::new(8.0, DamageKind::Kinetic),
+        );
+        hurt(&mut app, target, None, Damage::new(2.0, DamageKind::Fire));
+        app.update();
+
+        assert_eq!(app.world.get::<Health>(target).unwrap().current, 4.0);
+        assert!(deaths(&app).is_empty());
+    }
+
+    #[test]
+    fn dying_despawns_by_default() {
+        let mut app = setup();
+        let killer = app.world.spawn_empty().id();
+        let target = app
+            .world
+            .spawn((Health::new(5.0), GlobalTransform::from_xyz(3.0, 4.0, 0.0)))
+            .id();
+
+        hurt(
+            &mut app,
+            target,
+            Some(killer),
+            Damage::new(10.0, DamageKind::Energy),
+        );
+        // Overkill doesn't kill it twice
+        hurt(
+            &mut app,
+            target,
+            None,
+            Damage::new(10.0, DamageKind::Energy),
+        );
+        app.update();
+
+        assert!(app.world.get_entity(target).is_none());
+        assert_eq!(
+            deaths(&app),
+            vec![Died {
+                entity: target,
+                killer: Some(killer),
+                position: Vec2::new(3.0, 4.0),
+            }]
+        );
+    }
+
+    #[test]
+    fn dying_can_leave_a_corpse() {
+        let mut app = setup();
+        let target = app
+            .world
+            .spawn((
+                Health::new(5.0),
+                OnDeath::LeaveCorpse,
+                Sprite::default(),
+                MoveIntent::default(),
+            ))
+            .id();
+
+        hurt(&mut app, target, None, Damage::new(5.0, DamageKind::Vacuum));
+        app.update();
+
+        let corpse = app.world.entity(target);
+        assert!(corpse.contains::<Corpse>());
+        assert!(!corpse.contains::<Health>());
+        assert!(!corpse.contains::<MoveIntent>());
+        assert_eq!(corpse.get::<Sprite>().unwrap().color, CORPSE_TINT);
+        assert_eq!(deaths(&app).len(), 1);
+
+        // Corpses can't be hurt, so can't die again
+        hurt(&mut app, target, None, Damage::new(5.0, DamageKind::Vacuum));
+        app.update();
+        assert!(app.world.get_entity(target).is_some());
+    }
+}
@@ -0,0 +1,219 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    core::OPAQUE_GROUP,
+    map::{tile_at, tile_center, TILE_SIZE},
+};
+
+/// How much louder a noise must be to be heard through a wall, in pixels of open space
+const WALL_DAMPING: f32 = 8.0 * TILE_SIZE;
+
+/// A noise made somewhere in the world
+///
+/// Noises spread out across the tile grid, going around obstacles where they can and getting
+/// quieter the further they travel, and much quieter through walls.
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub struct Noise {
+    /// Whoever made the noise, if anyone
+    pub source: Option<Entity>,
+    pub position: Vec2,
+    /// How far this noise carries through open space, in pixels
+    pub loudness: f32,
+}
+
+/// A noise as heard by something with `Hearing`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeardNoise {
+    pub source: Option<Entity>,
+    /// Direction the noise was heard from; this is the way it came in, e.g. through a doorway,
+    /// rather than necessarily straight toward its source
+    pub direction: Vec2,
+    /// How loud the noise was when heard, from `0.0` (inaudible) to `1.0` (right on top of it)
+    pub intensity: f32,
+}
+
+/// The ability to hear `Noise`s
+///
+/// Noises heard are collected every frame, for game logic (e.g. AI) to react to.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct Hearing {
+    /// The quietest intensity that can be heard
+    pub threshold: f32,
+    heard: Vec<HeardNoise>,
+}
+
+impl Default for Hearing {
+    fn default() -> Self {
+        Self::new(0.05)
+    }
+}
+
+impl Hearing {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            heard: Vec::new(),
+        }
+    }
+
+    /// The noises heard this frame
+    pub fn heard(&self) -> &[HeardNoise] {
+        &self.heard
+    }
+
+    /// The loudest noise heard this frame, if any
+    pub fn loudest(&self) -> Option<&HeardNoise> {
+        self.heard
+            .iter()
+            .max_by(|a, b| a.intensity.total_cmp(&b.intensity))
+    }
+}
+
+/// Makes a collider muffle sound passing through it by a specific amount, rather than the default
+/// for walls
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct SoundDamper {
+    /// How much louder a noise must be to be heard through this, in pixels of open space
+    pub damping: f32,
+}
+
+pub fn propagate_noise(
+    rapier_context: Res<RapierContext>,
+    mut noises: EventReader<Noise>,
+    mut listener_qry: Query<(Entity, &GlobalTransform, &mut Hearing)>,
+    damper_qry: Query<&SoundDamper>,
+) {
+    for (_, _, mut hearing) in listener_qry.iter_mut() {
+        hearing.heard.clear();
+    }
+
+    for noise in noises.read() {
+        let spread = spread_noise(noise, &rapier_context, &damper_qry);
+
+        for (listener, listener_transform, mut hearing) in listener_qry.iter_mut() {
+            if noise.source == Some(listener) {
+                // We know what noises we're making
+                continue;
+            }
+
+            let position = listener_transform.translation().truncate();
+            let Some(&(cost, from)) = spread.get(&tile_at(position)) else {
+                continue;
+            };
+            let intensity = 1.0 - cost / noise.loudness;
+            if intensity < hearing.threshold {
+                continue;
+            }
+
+            // If the noise is right here with us, we can hear exactly where it is; otherwise it
+            // comes from whichever way it reached us
+            let direction = match from {
+                Some(tile) => tile_center(tile) - position,
+                None => noise.position - position,
+            }
+            .normalize_or_zero();
+
+            hearing.heard.push(HeardNoise {
+                source: noise.source,
+                direction,
+                intensity,
+            });
+        }
+    }
+}
+
+/// A tile waiting to be visited while spreading a noise
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Frontier {
+    cost: f32,
+    tile: IVec2,
+}
+
+impl Eq for Frontier {}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed, so that our BinaryHeap pops the cheapest tile first
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Spread a noise across the tile grid, finding every tile it reaches
+///
+/// Returns, for each tile reached, how much loudness it took to get there, and the neighboring tile
+/// it got there from (`None` for the tile the noise was made in).
+fn spread_noise(
+    noise: &Noise,
+    rapier_context: &RapierContext,
+    damper_qry: &Query<&SoundDamper>,
+) -> HashMap<IVec2, (f32, Option<IVec2>)> {
+    let filter = QueryFilter::new().groups(CollisionGroups::new(Group::all(), OPAQUE_GROUP));
+
+    // How much a tile muffles sound, looked up as we need it
+    let mut damping = HashMap::new();
+    let mut tile_damping = |tile: IVec2| {
+        *damping.entry(tile).or_insert_with(|| {
+            let mut tile_damping: f32 = 0.0;
+            rapier_context.intersections_with_point(tile_center(tile), filter, |entity| {
+                let entity_damping = damper_qry
+                    .get(entity)
+                    .map(|damper| damper.damping)
+                    .unwrap_or(WALL_DAMPING);
+                tile_damping = tile_damping.max(entity_damping);
+                true
+            });
+            tile_damping
+        })
+    };
+
+    let start = tile_at(noise.position);
+    let mut reached = HashMap::new();
+    reached.insert(start, (0.0, None));
+    let mut frontier = BinaryHeap::from([Frontier {
+        cost: 0.0,
+        tile: start,
+    }]);
+
+    while let Some(Frontier { cost, tile }) = frontier.pop() {
+        if reached
+            .get(&tile)
+            .map(|&(best, _)| best < cost)
+            .unwrap_or(false)
+        {
+            // We've already found a quieter way here
+            continue;
+        }
+
+        for step in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+            let next = tile + step;
+            let next_cost = cost + TILE_SIZE + tile_damping(next);
+            if next_cost >= noise.loudness {
+                continue;
+            }
+            if reached
+                .get(&next)
+                .map(|&(best, _)| best <= next_cost)
+                .unwrap_or(false)
+            {
+                continue;
+            }
+
+            reached.insert(next, (next_cost, Some(tile)));
+            frontier.push(Frontier {
+                cost: next_cost,
+                tile: next,
+            });
+        }
+    }
+
+    reached
+}
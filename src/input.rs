@@ -0,0 +1,417 @@
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use bevy::{input::mouse::MouseMotion, prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+/// Where input bindings are loaded from, and saved to when they change
+pub const BINDINGS_PATH: &str = "config/bindings.ron";
+
+/// How far an action's input must go before the action counts as pressed, e.g. for analogue sticks
+const PRESS_THRESHOLD: f32 = 0.5;
+
+/// How far a gamepad stick must be pushed, or the mouse moved in pixels, to count as using it
+const STICK_SWITCH_THRESHOLD: f32 = 0.3;
+const MOUSE_SWITCH_THRESHOLD: f32 = 2.0;
+
+/// Something the player can do, independent of whichever input it's bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Sneak,
+    /// Walk to wherever the mouse cursor is
+    MoveTo,
+    /// Held while using `MoveTo`, to walk there after everywhere else already queued up
+    QueueMove,
+    Interact,
+    Fire,
+    Reload,
+    ToggleInventory,
+    /// Slow time down, or speed it back up
+    ToggleTactical,
+    /// Only let time pass while acting, or let it pass normally again
+    ToggleTurnBased,
+    /// Aiming with an analogue stick; aiming with the mouse follows the cursor instead
+    AimUp,
+    AimDown,
+    AimLeft,
+    AimRight,
+    ToggleFps,
+    QuickSave,
+    QuickLoad,
+    /// Pause the game, or resume it if it's paused
+    Pause,
+}
+
+/// A physical input that can trigger an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// A button on any connected gamepad
+    GamepadButton(GamepadButtonType),
+    /// One direction of an axis on any connected gamepad, e.g. pushing a stick left
+    GamepadAxis {
+        axis: GamepadAxisType,
+        /// `true` to trigger on positive values of the axis, `false` on negative values
+        positive: bool,
+    },
+}
+
+/// Which inputs trigger which actions
+///
+/// Bindings can be changed at any time, and are saved to `BINDINGS_PATH` whenever they are.
+#[derive(Debug, Clone, PartialEq, Eq, Resource, Serialize, Deserialize)]
+pub struct InputBindings(BTreeMap<Action, Vec<Binding>>);
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use Binding::*;
+
+        Self(BTreeMap::from([
+            (
+                Action::MoveUp,
+                vec![
+                    Key(KeyCode::W),
+                    GamepadAxis {
+                        axis: GamepadAxisType::LeftStickY,
+                        positive: true,
+                    },
+                ],
+            ),
+            (
+                Action::MoveDown,
+                vec![
+                    Key(KeyCode::S),
+                    GamepadAxis {
+                        axis: GamepadAxisType::LeftStickY,
+                        positive: false,
+                    },
+                ],
+            ),
+            (
+                Action::MoveLeft,
+                vec![
+                    Key(KeyCode::A),
+                    GamepadAxis {
+                        axis: GamepadAxisType::LeftStickX,
+                        positive: false,
+                    },
+                ],
+            ),
+            (
+                Action::MoveRight,
+                vec![
+                    Key(KeyCode::D),
+                    GamepadAxis {
+                        axis: GamepadAxisType::LeftStickX,
+                        positive: true,
+                    },
+                ],
+            ),
+            (
+                Action::Sneak,
+                vec![
+                    Key(KeyCode::ControlLeft),
+                    Key(KeyCode::ControlRight),
+                    GamepadButton(GamepadButtonType::LeftTrigger2),
+                ],
+            ),
+            (Action::MoveTo, vec![Mouse(MouseButton::Right)]),
+            (
+                Action::QueueMove,
+                vec![Key(KeyCode::ShiftLeft), Key(KeyCode::ShiftRight)],
+            ),
+            (
+                Action::Interact,
+                vec![Key(KeyCode::E), GamepadButton(GamepadButtonType::South)],
+            ),
+            (
+                Action::AimUp,
+                vec![GamepadAxis {
+                    axis: GamepadAxisType::RightStickY,
+                    positive: true,
+                }],
+            ),
+            (
+                Action::AimDown,
+                vec![GamepadAxis {
+                    axis: GamepadAxisType::RightStickY,
+                    positive: false,
+                }],
+            ),
+            (
+                Action::AimLeft,
+                vec![GamepadAxis {
+                    axis: GamepadAxisType::RightStickX,
+                    positive: false,
+                }],
+            ),
+            (
+                Action::AimRight,
+                vec![GamepadAxis {
+                    axis: GamepadAxisType::RightStickX,
+                    positive: true,
+                }],
+            ),
+            (
+                Action::Fire,
+                vec![
+                    Mouse(MouseButton::Left),
+                    GamepadButton(GamepadButtonType::RightTrigger2),
+                ],
+            ),
+            (
+                Action::Reload,
+                vec![Key(KeyCode::R), GamepadButton(GamepadButtonType::West)],
+            ),
+            (
+                Action::ToggleInventory,
+                vec![Key(KeyCode::I), GamepadButton(GamepadButtonType::Start)],
+            ),
+            (
+                Action::ToggleTactical,
+                vec![Key(KeyCode::Tab), GamepadButton(GamepadButtonType::North)],
+            ),
+            (Action::ToggleTurnBased, vec![Key(KeyCode::T)]),
+            (Action::ToggleFps, vec![Key(KeyCode::F12)]),
+            (Action::QuickSave, vec![Key(KeyCode::F5)]),
+            (Action::QuickLoad, vec![Key(KeyCode::F9)]),
+            (
+                Action::Pause,
+                vec![
+                    Key(KeyCode::Escape),
+                    GamepadButton(GamepadButtonType::Select),
+                ],
+            ),
+        ]))
+    }
+}
+
+impl InputBindings {
+    /// Load bindings from a RON file, on top of the defaults
+    ///
+    /// Any action the file doesn't mention, e.g. one added since it was saved, keeps its default
+    /// bindings; an action the file leaves unbound stays that way.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let loaded: Self = ron::from_str(&contents)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        let mut bindings = Self::default();
+        bindings.0.extend(loaded.0);
+        Ok(bindings)
+    }
+
+    /// Save bindings to a RON file, creating its directory if need be
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let contents = ron::ser::to_string_pretty(self, Default::default())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, contents)
+    }
+
+    /// The inputs bound to `action`
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.0.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Add another input that triggers `action`
+    pub fn bind(&mut self, action: Action, binding: Binding) {
+        let bindings = self.0.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Stop an input from triggering `action`
+    pub fn unbind(&mut self, action: Action, binding: Binding) {
+        if let Some(bindings) = self.0.get_mut(&action) {
+            bindings.retain(|&bound| bound != binding);
+        }
+    }
+
+    /// Replace every input that triggers `action`
+    pub fn rebind(&mut self, action: Action, bindings: Vec<Binding>) {
+        self.0.insert(action, bindings);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Action, &[Binding])> {
+        self.0
+            .iter()
+            .map(|(&action, bindings)| (action, bindings.as_slice()))
+    }
+}
+
+/// The current state of every action, updated from `InputBindings` at the start of each frame
+#[derive(Debug, Default, Clone, PartialEq, Resource)]
+pub struct ActionState {
+    values: HashMap<Action, f32>,
+    previous: HashMap<Action, f32>,
+}
+
+impl ActionState {
+    /// How far `action`'s input is pressed, from `0.0` (not at all) to `1.0` (all the way)
+    ///
+    /// Buttons and keys are only ever `0.0` or `1.0`; gamepad axes can be anywhere in between.
+    pub fn value(&self, action: Action) -> f32 {
+        self.values.get(&action).copied().unwrap_or(0.0)
+    }
+
+    /// Combine two opposing actions into one axis, from `-1.0` to `1.0`
+    pub fn axis(&self, negative: Action, positive: Action) -> f32 {
+        self.value(positive) - self.value(negative)
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.value(action) >= PRESS_THRESHOLD
+    }
+
+    pub fn any_pressed(&self, actions: impl IntoIterator<Item = Action>) -> bool {
+        actions.into_iter().any(|action| self.pressed(action))
+    }
+
+    /// Check if `action` was pressed this frame
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.pressed(action) && !self.was_pressed(action)
+    }
+
+    /// Check if `action` was released this frame
+    pub fn just_released(&self, action: Action) -> bool {
+        !self.pressed(action) && self.was_pressed(action)
+    }
+
+    fn was_pressed(&self, action: Action) -> bool {
+        self.previous.get(&action).copied().unwrap_or(0.0) >= PRESS_THRESHOLD
+    }
+}
+
+/// Whichever kind of input device the player used most recently
+///
+/// This decides, e.g., whether the player aims with the mouse cursor or a gamepad stick.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Resource)]
+pub enum LastInputDevice {
+    #[default]
+    KeyboardMouse,
+    Gamepad,
+}
+
+/// Load input bindings from `BINDINGS_PATH`, falling back to the defaults
+pub fn load_input_bindings(mut commands: Commands) {
+    let bindings = match InputBindings::load(BINDINGS_PATH) {
+        Ok(bindings) => bindings,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => InputBindings::default(),
+        Err(err) => {
+            warn!("Could not load input bindings from {BINDINGS_PATH}, using defaults: {err}");
+            InputBindings::default()
+        }
+    };
+
+    commands.insert_resource(bindings);
+}
+
+/// Save input bindings to `BINDINGS_PATH` whenever they're changed
+pub fn save_input_bindings(bindings: Res<InputBindings>) {
+    if !bindings.is_changed() || bindings.is_added() {
+        return;
+    }
+
+    if let Err(err) = bindings.save(BINDINGS_PATH) {
+        warn!("Could not save input bindings to {BINDINGS_PATH}: {err}");
+    }
+}
+
+/// Update every action from whatever inputs are bound to it
+///
+/// # Running this system
+///
+/// This system needs to run in the `PreUpdate` schedule after Bevy's `InputSystem`, so that
+/// actions are up to date before any game logic reads them.
+pub fn update_action_state(
+    bindings: Option<Res<InputBindings>>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut state: ResMut<ActionState>,
+) {
+    let Some(bindings) = bindings else {
+        return;
+    };
+
+    let binding_value = |binding: &Binding| match *binding {
+        Binding::Key(key) => f32::from(u8::from(keys.pressed(key))),
+        Binding::Mouse(button) => f32::from(u8::from(mouse_buttons.pressed(button))),
+        Binding::GamepadButton(button_type) => {
+            let pressed = gamepads
+                .iter()
+                .any(|gamepad| gamepad_buttons.pressed(GamepadButton::new(gamepad, button_type)));
+            f32::from(u8::from(pressed))
+        }
+        Binding::GamepadAxis { axis, positive } => gamepads
+            .iter()
+            .filter_map(|gamepad| gamepad_axes.get(GamepadAxis::new(gamepad, axis)))
+            .map(|value| if positive { value } else { -value })
+            .fold(0.0, f32::max),
+    };
+
+    let state = &mut *state;
+    std::mem::swap(&mut state.previous, &mut state.values);
+    state.values.clear();
+    for (action, action_bindings) in bindings.iter() {
+        let value = action_bindings
+            .iter()
+            .map(binding_value)
+            .fold(0.0, f32::max)
+            .min(1.0);
+        state.values.insert(action, value);
+    }
+}
+
+/// Keep track of which kind of input device was used last
+///
+/// Like `update_action_state`, this needs to run in `PreUpdate` after Bevy's `InputSystem`.
+pub fn update_last_input_device(
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut last_device: ResMut<LastInputDevice>,
+) {
+    let mouse_moved = mouse_motion.read().map(|motion| motion.delta).sum::<Vec2>();
+    let used_keyboard_mouse = keys.get_just_pressed().next().is_some()
+        || mouse_buttons.get_just_pressed().next().is_some()
+        || mouse_moved.length() >= MOUSE_SWITCH_THRESHOLD;
+
+    let sticks = [
+        GamepadAxisType::LeftStickX,
+        GamepadAxisType::LeftStickY,
+        GamepadAxisType::RightStickX,
+        GamepadAxisType::RightStickY,
+    ];
+    let used_gamepad = gamepad_buttons.get_just_pressed().next().is_some()
+        || gamepads.iter().any(|gamepad| {
+            sticks.iter().any(|&axis| {
+                gamepad_axes
+                    .get(GamepadAxis::new(gamepad, axis))
+                    .map(|value| value.abs() >= STICK_SWITCH_THRESHOLD)
+                    .unwrap_or(false)
+            })
+        });
+
+    // If both were used at once, stick with whatever we were already using
+    let device = match (used_keyboard_mouse, used_gamepad) {
+        (true, false) => LastInputDevice::KeyboardMouse,
+        (false, true) => LastInputDevice::Gamepad,
+        _ => return,
+    };
+    last_device.set_if_neq(device);
+}
@@ -0,0 +1,141 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    core::OPAQUE_GROUP,
+    input::{Action, ActionState},
+    player::Player,
+};
+
+/// How wide the arc in front of the player that they can interact with things in is
+pub const INTERACT_ARC: f32 = TAU / 3.0;
+
+/// How far away the player can interact with things
+pub const INTERACT_RANGE: f32 = 40.0;
+
+/// What sort of interaction something offers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InteractionKind {
+    /// Open or close something, e.g. a door
+    Open,
+    /// Use something, e.g. a terminal
+    Use,
+    /// Pick something up, e.g. loot
+    PickUp,
+    /// Flip something on or off, e.g. a switch
+    Toggle,
+}
+
+/// Something that can be interacted with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub struct Interactable {
+    pub kind: InteractionKind,
+}
+
+impl Interactable {
+    pub fn new(kind: InteractionKind) -> Self {
+        Self { kind }
+    }
+}
+
+/// Tag component for the interactable the player would interact with right now
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
+pub struct Highlighted;
+
+/// Sent whenever something is interacted with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub struct Interact {
+    /// Who is doing the interacting
+    pub actor: Entity,
+    /// What they're interacting with
+    pub target: Entity,
+    pub kind: InteractionKind,
+}
+
+/// Check that nothing opaque stands between `from` and `target`, at `to`
+///
+/// The target itself may well be opaque, e.g. a closed door, so is ignored.
+fn in_line_of_sight(rapier_context: &RapierContext, from: Vec2, target: Entity, to: Vec2) -> bool {
+    let filter = QueryFilter::new()
+        .exclude_sensors()
+        .groups(CollisionGroups::new(Group::all(), OPAQUE_GROUP))
+        .exclude_collider(target);
+    let solid = true;
+
+    rapier_context
+        .cast_ray(from, to - from, 1.0, solid, filter)
+        .is_none()
+}
+
+/// Highlight the nearest interactable within the player's reach and facing arc, that they can see
+pub fn highlight_interactable(
+    mut commands: Commands,
+    rapier_context: Res<RapierContext>,
+    player_qry: Query<&GlobalTransform, With<Player>>,
+    interactable_qry: Query<(Entity, &GlobalTransform), With<Interactable>>,
+    highlighted_qry: Query<Entity, With<Highlighted>>,
+) {
+    let nearest = player_qry.get_single().ok().and_then(|player_transform| {
+        let position = player_transform.translation().truncate();
+        let facing = player_transform.right().truncate();
+
+        interactable_qry
+            .iter()
+            .map(|(entity, transform)| (entity, transform.translation().truncate() - position))
+            .filter(|&(entity, offset)| {
+                offset.length() <= INTERACT_RANGE
+                    && facing.angle_between(offset).abs() <= INTERACT_ARC / 2.0
+                    // No reaching through walls
+                    && in_line_of_sight(&rapier_context, position, entity, position + offset)
+            })
+            .min_by(|(_, a), (_, b)| a.length_squared().total_cmp(&b.length_squared()))
+            .map(|(entity, _)| entity)
+    });
+
+    for highlighted in highlighted_qry.iter() {
+        if Some(highlighted) != nearest {
+            commands.entity(highlighted).remove::<Highlighted>();
+        }
+    }
+    if let Some(nearest) = nearest {
+        if !highlighted_qry.contains(nearest) {
+            commands.entity(nearest).insert(Highlighted);
+        }
+    }
+}
+
+pub fn draw_highlight(
+    highlighted_qry: Query<&GlobalTransform, With<Highlighted>>,
+    mut gizmos: Gizmos,
+) {
+    for transform in highlighted_qry.iter() {
+        gizmos.circle_2d(
+            transform.translation().truncate(),
+            12.0,
+            Color::YELLOW.with_a(0.75),
+        );
+    }
+}
+
+pub fn player_interact(
+    actions: Res<ActionState>,
+    player_qry: Query<Entity, With<Player>>,
+    target_qry: Query<(Entity, &Interactable), With<Highlighted>>,
+    mut interactions: EventWriter<Interact>,
+) {
+    if !actions.just_pressed(Action::Interact) {
+        return;
+    }
+
+    if let Ok(player) = player_qry.get_single() {
+        for (target, interactable) in target_qry.iter() {
+            interactions.send(Interact {
+                actor: player,
+                target,
+                kind: interactable.kind,
+            });
+        }
+    }
+}
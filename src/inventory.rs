@@ -0,0 +1,563 @@
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fov::{FieldOfView, Viewable},
+    health::Health,
+    interaction::{Interact, Interactable, InteractionKind},
+    locomotion::Locomotion,
+    map::{tile_center, Rooms, ShipParameters},
+    rand::*,
+};
+
+/// Where item definitions are loaded from
+pub const ITEMS_PATH: &str = "assets/items.ron";
+
+/// How likely each room is to have an item lying around in it
+const ROOM_ITEM_CHANCE: f64 = 0.5;
+
+const ITEM_Z: f32 = 2.0;
+
+/// Identifies a kind of item, as named in the item definitions file
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ItemId(pub String);
+
+impl From<&str> for ItemId {
+    fn from(value: &str) -> Self {
+        Self(value.into())
+    }
+}
+
+/// Where an item can be equipped; only one item can be equipped in each slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum EquipSlot {
+    Head,
+    Body,
+    Legs,
+    Sensor,
+}
+
+/// How an equipped item changes its wearer's stats; every field is added to the matching stat
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatModifiers {
+    /// Added to `FieldOfView::view_distance`
+    pub view_distance: f32,
+    /// Added to `FieldOfView::dark_vision`
+    pub dark_vision: f32,
+    /// Added to `Locomotion::walk_speed`
+    pub walk_speed: f32,
+    /// Added to `Health::max`
+    pub max_health: f32,
+}
+
+impl StatModifiers {
+    fn plus(self, other: Self) -> Self {
+        Self {
+            view_distance: self.view_distance + other.view_distance,
+            dark_vision: self.dark_vision + other.dark_vision,
+            walk_speed: self.walk_speed + other.walk_speed,
+            max_health: self.max_health + other.max_health,
+        }
+    }
+
+    fn minus(self, other: Self) -> Self {
+        Self {
+            view_distance: self.view_distance - other.view_distance,
+            dark_vision: self.dark_vision - other.dark_vision,
+            walk_speed: self.walk_speed - other.walk_speed,
+            max_health: self.max_health - other.max_health,
+        }
+    }
+}
+
+/// Everything about a kind of item
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemDef {
+    pub name: String,
+    pub weight: f32,
+    /// Color of the item when it's lying around
+    pub color: (f32, f32, f32),
+    /// Where this item can be equipped, if it can be at all
+    #[serde(default)]
+    pub slot: Option<EquipSlot>,
+    #[serde(default)]
+    pub modifiers: StatModifiers,
+}
+
+/// Every kind of item, loaded from `ITEMS_PATH`
+#[derive(Debug, Default, Clone, PartialEq, Resource, Serialize, Deserialize)]
+pub struct ItemDefinitions(BTreeMap<ItemId, ItemDef>);
+
+impl ItemDefinitions {
+    /// Load item definitions from a RON file
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        ron::from_str(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn get(&self, id: &ItemId) -> Option<&ItemDef> {
+        self.0.get(id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&ItemId, &ItemDef)> {
+        self.0.iter()
+    }
+
+    /// The display name of an item
+    pub fn name(&self, id: &ItemId) -> &str {
+        self.get(id).map(|def| def.name.as_str()).unwrap_or("???")
+    }
+
+    /// How heavy an item is; unknown items weigh nothing
+    pub fn weight(&self, id: &ItemId) -> f32 {
+        self.get(id).map(|def| def.weight).unwrap_or(0.0)
+    }
+}
+
+/// An item lying around in the world, waiting to be picked up
+#[derive(Debug, Clone, PartialEq, Eq, Component)]
+pub struct Item(pub ItemId);
+
+/// Items carried by something, and which of them it has equipped
+///
+/// Equipped items are still carried, and count toward the weight limit, but are kept separately
+/// from the rest.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct Inventory {
+    /// The most weight that can be carried
+    pub max_weight: f32,
+    items: Vec<ItemId>,
+    equipped: HashMap<EquipSlot, ItemId>,
+    /// The stat modifiers currently applied for equipped items
+    applied: StatModifiers,
+}
+
+impl Inventory {
+    pub fn new(max_weight: f32) -> Self {
+        Self {
+            max_weight,
+            items: Vec::new(),
+            equipped: HashMap::new(),
+            applied: StatModifiers::default(),
+        }
+    }
+
+    /// Items carried but not equipped
+    pub fn items(&self) -> &[ItemId] {
+        &self.items
+    }
+
+    pub fn equipped(&self, slot: EquipSlot) -> Option<&ItemId> {
+        self.equipped.get(&slot)
+    }
+
+    /// Every equipped item, and the slot it's equipped in
+    pub fn all_equipped(&self) -> impl Iterator<Item = (EquipSlot, &ItemId)> {
+        self.equipped.iter().map(|(&slot, id)| (slot, id))
+    }
+
+    /// Replace everything carried, e.g. when loading a saved game
+    ///
+    /// Stat modifiers are updated to match whatever is now equipped by `apply_equipment`, as usual.
+    pub fn replace(
+        &mut self,
+        items: Vec<ItemId>,
+        equipped: impl IntoIterator<Item = (EquipSlot, ItemId)>,
+    ) {
+        self.items = items;
+        self.equipped = equipped.into_iter().collect();
+    }
+
+    /// How much everything carried, equipped or not, weighs
+    pub fn weight(&self, defs: &ItemDefinitions) -> f32 {
+        self.items
+            .iter()
+            .chain(self.equipped.values())
+            .map(|id| defs.weight(id))
+            .sum()
+    }
+
+    /// Add an item, if it isn't too heavy to carry
+    ///
+    /// Returns `false` if the item was too heavy.
+    pub fn add(&mut self, id: ItemId, defs: &ItemDefinitions) -> bool {
+        if self.weight(defs) + defs.weight(&id) > self.max_weight {
+            return false;
+        }
+        self.items.push(id);
+        true
+    }
+
+    /// Take out the (unequipped) item at `index`
+    pub fn remove(&mut self, index: usize) -> Option<ItemId> {
+        (index < self.items.len()).then(|| self.items.remove(index))
+    }
+
+    /// Equip the item at `index`, swapping out whatever was already in its slot
+    ///
+    /// Returns `false` if the item can't be equipped.
+    pub fn equip(&mut self, index: usize, defs: &ItemDefinitions) -> bool {
+        let Some(slot) = self
+            .items
+            .get(index)
+            .and_then(|id| defs.get(id))
+            .and_then(|def| def.slot)
+        else {
+            return false;
+        };
+
+        let id = self.items.remove(index);
+        if let Some(previous) = self.equipped.insert(slot, id) {
+            self.items.push(previous);
+        }
+        true
+    }
+
+    /// Stop using whatever is equipped in `slot`, keeping it in the inventory
+    pub fn unequip(&mut self, slot: EquipSlot) {
+        if let Some(id) = self.equipped.remove(&slot) {
+            self.items.push(id);
+        }
+    }
+
+    fn modifiers(&self, defs: &ItemDefinitions) -> StatModifiers {
+        self.equipped
+            .values()
+            .filter_map(|id| defs.get(id))
+            .fold(StatModifiers::default(), |total, def| {
+                total.plus(def.modifiers)
+            })
+    }
+}
+
+/// Load item definitions from `ITEMS_PATH`
+pub fn load_item_definitions(mut commands: Commands) {
+    let defs = ItemDefinitions::load(ITEMS_PATH).unwrap_or_else(|err| {
+        warn!("Could not load item definitions from {ITEMS_PATH}: {err}");
+        ItemDefinitions::default()
+    });
+
+    commands.insert_resource(defs);
+}
+
+/// Spawn an item lying around in the world
+pub fn spawn_item(
+    commands: &mut Commands,
+    defs: &ItemDefinitions,
+    id: ItemId,
+    position: Vec2,
+) -> Entity {
+    let (r, g, b) = defs
+        .get(&id)
+        .map(|def| def.color)
+        .unwrap_or((1.0, 0.0, 1.0));
+
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgb(r, g, b),
+                    custom_size: Some(Vec2::splat(8.0)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(position.extend(ITEM_Z)),
+                ..Default::default()
+            },
+            Item(id),
+            Interactable::new(InteractionKind::PickUp),
+            Viewable::Static,
+        ))
+        .id()
+}
+
+/// Scatter items around the ship's rooms, the same way every time for a given ship
+pub fn spawn_room_items(
+    mut commands: Commands,
+    rooms: Res<Rooms>,
+    ship: Res<ShipParameters>,
+    defs: Res<ItemDefinitions>,
+) {
+    let ids = defs.iter().map(|(id, _)| id.clone()).collect::<Vec<_>>();
+    if ids.is_empty() {
+        return;
+    }
+
+    let mut rng = seed_rng((ship.seed, "items"));
+    for room in rooms.iter() {
+        if !rng.gen_bool(ROOM_ITEM_CHANCE) {
+            continue;
+        }
+        // Keep items off the room's edges, where the walls and doors are
+        let inner = room.inset(-1);
+        if inner.is_empty() {
+            continue;
+        }
+        let tile = IVec2::new(
+            rng.gen_range(inner.min.x..inner.max.x),
+            rng.gen_range(inner.min.y..inner.max.y),
+        );
+        let id = ids[rng.gen_range(0..ids.len())].clone();
+        spawn_item(&mut commands, &defs, id, tile_center(tile));
+    }
+}
+
+/// Pick up items that are interacted with, if the one picking them up can carry them
+pub fn pick_up_items(
+    mut commands: Commands,
+    defs: Res<ItemDefinitions>,
+    mut interactions: EventReader<Interact>,
+    item_qry: Query<&Item>,
+    mut inventory_qry: Query<&mut Inventory>,
+) {
+    for interaction in interactions
+        .read()
+        .filter(|interaction| interaction.kind == InteractionKind::PickUp)
+    {
+        let (Ok(Item(id)), Ok(mut inventory)) = (
+            item_qry.get(interaction.target),
+            inventory_qry.get_mut(interaction.actor),
+        ) else {
+            continue;
+        };
+
+        if inventory.add(id.clone(), &defs) {
+            commands.entity(interaction.target).despawn_recursive();
+        } else {
+            debug!("{:?} is too heavy for {:?}", id, interaction.actor);
+        }
+    }
+}
+
+/// Drop the (unequipped) item at `index` from `inventory`, leaving it at `position`
+pub fn drop_item(
+    commands: &mut Commands,
+    defs: &ItemDefinitions,
+    inventory: &mut Inventory,
+    index: usize,
+    position: Vec2,
+) {
+    if let Some(id) = inventory.remove(index) {
+        spawn_item(commands, defs, id, position);
+    }
+}
+
+/// Everything an equipped item's stat modifiers can apply to
+type WearerStats = (
+    &'static mut Inventory,
+    Option<&'static mut FieldOfView>,
+    Option<&'static mut Locomotion>,
+    Option<&'static mut Health>,
+);
+
+/// Apply the stat modifiers of equipped items to whoever has them equipped
+pub fn apply_equipment(
+    defs: Res<ItemDefinitions>,
+    mut wearer_qry: Query<WearerStats, Changed<Inventory>>,
+) {
+    for (mut inventory, fov, locomotion, health) in wearer_qry.iter_mut() {
+        let modifiers = inventory.modifiers(&defs);
+        let change = modifiers.minus(inventory.applied);
+        if change == StatModifiers::default() {
+            continue;
+        }
+
+        if let Some(mut fov) = fov {
+            fov.view_distance += change.view_distance;
+            fov.dark_vision += change.dark_vision;
+        }
+        if let Some(mut locomotion) = locomotion {
+            locomotion.walk_speed += change.walk_speed;
+        }
+        if let Some(mut health) = health {
+            health.max += change.max_health;
+            health.current = health.current.min(health.max);
+        }
+
+        // Don't let keeping track of this count as the inventory changing again
+        inventory.bypass_change_detection().applied = modifiers;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(weight: f32, slot: Option<EquipSlot>, modifiers: StatModifiers) -> ItemDef {
+        ItemDef {
+            name: String::new(),
+            weight,
+            color: (1.0, 1.0, 1.0),
+            slot,
+            modifiers,
+        }
+    }
+
+    fn ids(names: &[&str]) -> Vec<ItemId> {
+        names.iter().map(|&name| name.into()).collect()
+    }
+
+    fn defs() -> ItemDefinitions {
+        ItemDefinitions(BTreeMap::from([
+            (
+                "helmet".into(),
+                item(
+                    3.0,
+                    Some(EquipSlot::Head),
+                    StatModifiers {
+                        view_distance: 10.0,
+                        max_health: 20.0,
+                        ..Default::default()
+                    },
+                ),
+            ),
+            (
+                "visor".into(),
+                item(
+                    2.0,
+                    Some(EquipSlot::Head),
+                    StatModifiers {
+                        dark_vision: 5.0,
+                        ..Default::default()
+                    },
+                ),
+            ),
+            (
+                "boots".into(),
+                item(
+                    4.0,
+                    Some(EquipSlot::Legs),
+                    StatModifiers {
+                        walk_speed: 30.0,
+                        ..Default::default()
+                    },
+                ),
+            ),
+            ("crate".into(), item(15.0, None, StatModifiers::default())),
+        ]))
+    }
+
+    #[test]
+    fn add_respects_max_weight() {
+        let defs = defs();
+        let mut inventory = Inventory::new(20.0);
+
+        assert!(inventory.add("crate".into(), &defs));
+        assert!(inventory.add("helmet".into(), &defs));
+        assert!(!inventory.add("boots".into(), &defs));
+        // Exactly at the limit is fine
+        assert!(inventory.add("visor".into(), &defs));
+        assert_eq!(inventory.weight(&defs), 20.0);
+        assert_eq!(inventory.items(), ids(&["crate", "helmet", "visor"]));
+    }
+
+    #[test]
+    fn equip_swaps_out_whatever_is_in_the_slot() {
+        let defs = defs();
+        let mut inventory = Inventory::new(20.0);
+        for id in ["helmet", "visor", "crate"] {
+            assert!(inventory.add(id.into(), &defs));
+        }
+
+        assert!(inventory.equip(0, &defs));
+        assert_eq!(inventory.equipped(EquipSlot::Head), Some(&"helmet".into()));
+        assert_eq!(inventory.items(), ids(&["visor", "crate"]));
+
+        assert!(inventory.equip(0, &defs));
+        assert_eq!(inventory.equipped(EquipSlot::Head), Some(&"visor".into()));
+        assert_eq!(inventory.items(), ids(&["crate", "helmet"]));
+
+        // Not everything can be equipped, and nothing can be equipped that isn't there
+        assert!(!inventory.equip(0, &defs));
+        assert!(!inventory.equip(5, &defs));
+
+        // Equipped items still weigh just as much
+        assert_eq!(inventory.weight(&defs), 20.0);
+        assert!(!inventory.add("boots".into(), &defs));
+    }
+
+    #[test]
+    fn unequip_keeps_the_item() {
+        let defs = defs();
+        let mut inventory = Inventory::new(20.0);
+        assert!(inventory.add("boots".into(), &defs));
+        assert!(inventory.equip(0, &defs));
+
+        inventory.unequip(EquipSlot::Head);
+        assert_eq!(inventory.equipped(EquipSlot::Legs), Some(&"boots".into()));
+
+        inventory.unequip(EquipSlot::Legs);
+        assert_eq!(inventory.equipped(EquipSlot::Legs), None);
+        assert_eq!(inventory.items(), ids(&["boots"]));
+        assert_eq!(inventory.weight(&defs), 4.0);
+    }
+
+    #[test]
+    fn apply_equipment_applies_only_the_change() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(defs())
+            .add_systems(Update, apply_equipment);
+
+        let mut inventory = Inventory::new(20.0);
+        inventory.replace(
+            vec!["visor".into()],
+            [
+                (EquipSlot::Head, ItemId::from("helmet")),
+                (EquipSlot::Legs, ItemId::from("boots")),
+            ],
+        );
+        let locomotion = Locomotion::default();
+        let wearer = app
+            .world
+            .spawn((
+                inventory,
+                FieldOfView::new(100.0, 1.0),
+                locomotion,
+                Health::new(50.0),
+            ))
+            .id();
+
+        app.update();
+        let entity = app.world.entity(wearer);
+        assert_eq!(entity.get::<FieldOfView>().unwrap().view_distance, 110.0);
+        assert_eq!(
+            entity.get::<Locomotion>().unwrap().walk_speed,
+            locomotion.walk_speed + 30.0
+        );
+        assert_eq!(entity.get::<Health>().unwrap().max, 70.0);
+
+        // Nothing's changed, so nothing's applied again
+        app.update();
+        assert_eq!(
+            app.world.get::<FieldOfView>(wearer).unwrap().view_distance,
+            110.0
+        );
+
+        // Swap the helmet for the visor, and take the boots off
+        app.world.get_mut::<Health>(wearer).unwrap().current = 70.0;
+        {
+            let mut inventory = app.world.get_mut::<Inventory>(wearer).unwrap();
+            let defs = defs();
+            assert!(inventory.equip(0, &defs));
+            inventory.unequip(EquipSlot::Legs);
+        }
+        app.update();
+        let entity = app.world.entity(wearer);
+        let fov = entity.get::<FieldOfView>().unwrap();
+        assert_eq!(fov.view_distance, 100.0);
+        assert_eq!(
+            fov.dark_vision,
+            FieldOfView::new(100.0, 1.0).dark_vision + 5.0
+        );
+        assert_eq!(
+            entity.get::<Locomotion>().unwrap().walk_speed,
+            locomotion.walk_speed
+        );
+        // Losing maximum health takes current health with it
+        assert_eq!(*entity.get::<Health>().unwrap(), Health::new(50.0));
+    }
+}
@@ -1,18 +1,34 @@
 use core::GameState;
 
-use bevy::{prelude::*, transform::TransformSystem};
+use bevy::{input::InputSystem, prelude::*, transform::TransformSystem};
+use bevy_egui::EguiPlugin;
 use bevy_rapier2d::prelude::*;
 use map::ShipParameters;
 
 pub mod ai;
 pub mod camera;
+pub mod combat;
 pub mod core;
+pub mod door;
 pub mod fov;
+pub mod hazard;
+pub mod health;
+pub mod hearing;
+pub mod input;
+pub mod interaction;
+pub mod inventory;
+pub mod lighting;
+pub mod locomotion;
 pub mod map;
+pub mod menu;
+pub mod pathfinding;
 pub mod player;
 pub mod rand;
+pub mod save;
 pub mod setup;
+pub mod ship_systems;
 pub mod sprites;
+pub mod time_control;
 pub mod ui;
 
 pub fn run_game() {
@@ -25,6 +41,7 @@ pub fn run_game() {
         ..Default::default()
     }))
     .add_plugins(bevy::diagnostic::FrameTimeDiagnosticsPlugin)
+    .add_plugins(EguiPlugin)
     .add_plugins(RapierPhysicsPlugin::<()>::pixels_per_meter(32.0))
     .insert_resource(RapierConfiguration {
         gravity: Vec2::ZERO, // In a top-down view, Rapier doesn't "see" gravity
@@ -41,12 +58,41 @@ pub fn run_game() {
         .insert_resource(rand::world_seed("Test seed"));
 
     app.init_resource::<ShipParameters>()
-        .add_systems(Update, bevy::window::close_on_esc)
+        .init_resource::<fov::ExploredMap>()
+        .init_resource::<lighting::LightMap>()
+        .init_resource::<lighting::AmbientLightLevel>()
+        .add_event::<hearing::Noise>()
+        .add_event::<interaction::Interact>()
+        .add_event::<door::DoorChanged>()
+        .add_event::<health::TakeDamage>()
+        .add_event::<health::Died>()
+        .add_event::<combat::FireWeapon>()
+        .init_resource::<input::ActionState>()
+        .init_resource::<input::LastInputDevice>()
+        .init_resource::<time_control::TimeControl>()
+        .init_resource::<menu::NewGameSettings>()
+        .add_systems(
+            PreUpdate,
+            (input::update_action_state, input::update_last_input_device).after(InputSystem),
+        )
+        .add_systems(
+            Update,
+            (
+                input::save_input_bindings,
+                (
+                    time_control::player_time_control,
+                    time_control::update_time_control.after(time_control::player_time_control),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            ),
+        )
         .add_state::<core::GameState>()
         .add_systems(
             Startup,
             (
                 camera::spawn_camera,
+                input::load_input_bindings,
+                inventory::load_item_definitions,
                 sprites::load_sprites,
                 ui::setup_fps_counter,
             ),
@@ -54,35 +100,193 @@ pub fn run_game() {
         .add_systems(
             Update,
             (
-                core::advance_game_state,
                 player::player_debug,
-                fov::add_fov,
-                fov::update_fov.after(fov::add_fov),
-                fov::update_viewables,
-                ai::drone_idle,
                 (
+                    ai::drone_idle,
+                    hearing::propagate_noise
+                        .after(player::player_footsteps)
+                        .after(combat::fire_weapons),
                     player::player_walk,
                     player::player_face,
+                    player::player_click_to_move,
+                    pathfinding::follow_waypoints
+                        .after(player::player_walk)
+                        .after(player::player_click_to_move),
+                    locomotion::apply_locomotion
+                        .after(pathfinding::follow_waypoints)
+                        .after(player::player_face),
+                    player::player_footsteps,
+                    interaction::highlight_interactable,
+                    interaction::player_interact.after(interaction::highlight_interactable),
+                    fov::update_explored_chunks.after(fov::resize_explored_map),
                     // map::debug_triangulation,
                 )
                     .run_if(in_state(GameState::InGame)),
+                interaction::draw_highlight,
                 ui::update_fps,
                 ui::toggle_fps_counter,
             ),
         )
+        // Field of view and everything that affects it
+        .add_systems(
+            Update,
+            (
+                fov::add_fov,
+                fov::update_fov.after(fov::add_fov),
+                fov::remove_fov,
+                fov::resize_fov.after(fov::add_fov),
+                fov::resize_explored_map,
+                fov::update_fog_overlay
+                    .after(fov::update_fov)
+                    .after(fov::resize_explored_map),
+                fov::update_viewables,
+                fov::add_visible_entities,
+                fov::update_visible_entities
+                    .after(fov::add_visible_entities)
+                    .after(lighting::update_light_map),
+                fov::update_ghosts.after(fov::update_visible_entities),
+                lighting::resize_light_map.after(fov::resize_explored_map),
+                lighting::update_light_map
+                    .after(lighting::resize_light_map)
+                    .run_if(lighting::lighting_changed),
+                lighting::update_darkness_overlay.after(lighting::update_light_map),
+            )
+                .run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(
+            Update,
+            (
+                door::spawn_doors.run_if(resource_added::<map::Rooms>()),
+                inventory::spawn_room_items.run_if(resource_added::<map::Rooms>()),
+                (
+                    door::operate_doors.after(interaction::player_interact),
+                    door::update_door_colliders.after(door::operate_doors),
+                    door::animate_doors.after(door::operate_doors),
+                    pathfinding::invalidate_paths
+                        .after(door::update_door_colliders)
+                        .before(pathfinding::follow_waypoints),
+                    inventory::pick_up_items.after(interaction::player_interact),
+                    inventory::apply_equipment
+                        .after(inventory::pick_up_items)
+                        .after(ui::inventory_panel),
+                    ui::inventory_panel,
+                )
+                    .run_if(in_state(GameState::InGame)),
+                pathfinding::draw_waypoints,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                combat::cool_down_weapons,
+                combat::player_fire,
+                ai::drone_attack,
+                combat::fire_weapons
+                    .after(combat::cool_down_weapons)
+                    .after(combat::player_fire)
+                    .after(ai::drone_attack),
+                combat::projectile_hits,
+                combat::expire_projectiles,
+                combat::fade_shot_effects,
+                combat::reveal_heard_shots.after(hearing::propagate_noise),
+                health::apply_damage
+                    .after(combat::fire_weapons)
+                    .after(combat::projectile_hits),
+                health::handle_deaths.after(health::apply_damage),
+            )
+                .run_if(in_state(GameState::InGame)),
+        )
+        // Power, life support, atmosphere and hazards
+        .add_systems(
+            Update,
+            (
+                ship_systems::setup_ship_systems.run_if(resource_added::<map::Rooms>()),
+                (
+                    ship_systems::distribute_power,
+                    ship_systems::apply_decompression
+                        .after(hazard::simulate_hazards)
+                        .after(locomotion::apply_locomotion),
+                    ship_systems::suffocate
+                        .after(hazard::simulate_hazards)
+                        .before(health::apply_damage),
+                )
+                    .run_if(resource_exists::<ship_systems::ShipSystems>())
+                    .run_if(in_state(GameState::InGame)),
+                hazard::setup_hazards.run_if(resource_added::<map::Rooms>()),
+                (
+                    // Also moves the ship's air around, on the same tick as its hazards
+                    hazard::simulate_hazards
+                        .after(ship_systems::distribute_power)
+                        .after(door::update_door_colliders),
+                    hazard::hazard_damage
+                        .after(hazard::simulate_hazards)
+                        .before(health::apply_damage),
+                )
+                    .run_if(resource_exists::<hazard::HazardMap>())
+                    .run_if(in_state(GameState::InGame)),
+                hazard::draw_hazards.run_if(resource_exists::<hazard::HazardMap>()),
+            ),
+        )
+        // Quick saving and loading
+        .add_systems(
+            Update,
+            (
+                save::quick_save
+                    .pipe(save::write_quick_save)
+                    .run_if(save::quick_save_pressed),
+                save::quick_load,
+            )
+                .run_if(in_state(GameState::InGame)),
+        )
+        // Apply saved games once everything spawned in Update exists, but before it's drawn
+        .add_systems(
+            PostUpdate,
+            save::apply_pending_load
+                .before(TransformSystem::TransformPropagate)
+                .run_if(resource_exists::<save::PendingLoad>())
+                .run_if(in_state(GameState::InGame)),
+        )
         // Update camera position in PostUpdate, but before Bevy propagates Transform to GlobalTransform
         .add_systems(
             PostUpdate,
             camera::follow_entity.after(TransformSystem::TransformPropagate),
         )
+        // Game states, and the menus for each of them
         .add_systems(
-            OnEnter(GameState::InGame),
+            Update,
             (
-                // map::setup_map,
-                player::spawn_player,
-                fov::setup_fog_of_war,
-                setup::setup_test_entities,
+                (core::finish_loading, menu::loading_screen).run_if(in_state(GameState::Loading)),
+                menu::main_menu.run_if(in_state(GameState::MainMenu)),
+                core::toggle_pause
+                    .run_if(in_state(GameState::InGame).or_else(in_state(GameState::Paused))),
+                menu::pause_menu.run_if(in_state(GameState::Paused)),
+                core::end_game_on_death
+                    .after(health::apply_damage)
+                    .run_if(in_state(GameState::InGame)),
+                menu::game_over_menu.run_if(in_state(GameState::GameOver)),
             ),
         )
+        .add_systems(OnEnter(GameState::MainMenu), core::teardown_game)
+        .add_systems(OnEnter(GameState::Paused), core::stop_time)
+        .add_systems(OnEnter(GameState::GameOver), core::stop_time)
+        // Only build a new world when starting a game, not when resuming one
+        .add_systems(
+            OnTransition {
+                from: GameState::MainMenu,
+                to: GameState::InGame,
+            },
+            (
+                map::setup_map,
+                // The player starts out in one of the ship's rooms, so they need to exist first
+                apply_deferred,
+                (
+                    player::spawn_player,
+                    fov::setup_fog_of_war,
+                    lighting::setup_darkness_overlay,
+                    setup::setup_test_entities,
+                ),
+            )
+                .chain(),
+        )
         .run();
 }
@@ -0,0 +1,314 @@
+use std::f32::consts::PI;
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        view::RenderLayers,
+    },
+};
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    core::{OPAQUE_GROUP, TRANSLUCENT_GROUP},
+    fov::{cast_view_cone, tiles_in_view, ExploredMap, FieldOfView, Occluder},
+    map::{tile_at, tile_center, TileGrid, TILE_SIZE},
+};
+
+/// The least amount of light something needs to be seen beyond a viewer's `dark_vision`
+pub const MIN_VISIBLE_LIGHT: f32 = 0.2;
+
+/// How dark the darkness overlay gets where there's no light at all
+const MAX_DARKNESS: f32 = 0.9;
+
+/// Light level everywhere, even without any light sources
+#[derive(Debug, Clone, Copy, PartialEq, Resource)]
+pub struct AmbientLightLevel(pub f32);
+
+impl Default for AmbientLightLevel {
+    fn default() -> Self {
+        Self(0.1)
+    }
+}
+
+/// A light that shines out from an entity, blocked by anything that would block sight
+///
+/// Light fades out linearly toward the edge of its `radius`.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct LightSource {
+    pub radius: f32,
+    /// Half the angle of the light's cone, centered on the entity's facing; π or more shines all
+    /// the way around
+    pub half_angle: f32,
+    pub intensity: f32,
+}
+
+impl LightSource {
+    /// A light that shines in every direction, e.g. a lamp
+    pub fn point(radius: f32, intensity: f32) -> Self {
+        Self {
+            radius,
+            half_angle: PI,
+            intensity,
+        }
+    }
+
+    /// A light that shines in a cone, e.g. a spotlight
+    pub fn cone(radius: f32, half_angle: f32, intensity: f32) -> Self {
+        Self {
+            radius,
+            half_angle,
+            intensity,
+        }
+    }
+}
+
+/// A light that evenly lights an entire room, but only while it has power
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct RoomLight {
+    /// The tiles lit by this light
+    pub bounds: IRect,
+    pub intensity: f32,
+    pub powered: bool,
+}
+
+/// Light level of every tile
+#[derive(Debug, Clone, PartialEq, Resource)]
+pub struct LightMap {
+    levels: TileGrid<f32>,
+}
+
+impl Default for LightMap {
+    fn default() -> Self {
+        Self::new(ExploredMap::default_bounds(), 0.0)
+    }
+}
+
+impl LightMap {
+    /// Create a new map covering `bounds`, evenly lit to `level`
+    pub fn new(bounds: IRect, level: f32) -> Self {
+        Self {
+            levels: TileGrid::new(bounds, level),
+        }
+    }
+
+    /// The tiles covered by this map
+    pub fn bounds(&self) -> IRect {
+        self.levels.bounds()
+    }
+
+    /// Light level of `tile`, from `0.0` (pitch black) to `1.0` (fully lit)
+    ///
+    /// Tiles outside of the map's bounds are always pitch black.
+    pub fn level(&self, tile: IVec2) -> f32 {
+        self.levels
+            .get(tile)
+            .map(|level| level.min(1.0))
+            .unwrap_or(0.0)
+    }
+
+    /// Light level of the tile containing the world-space `point`
+    pub fn level_at(&self, point: Vec2) -> f32 {
+        self.level(tile_at(point))
+    }
+
+    /// Check if the world-space `point` is lit well enough to be seen
+    pub fn is_visible(&self, point: Vec2) -> bool {
+        self.level_at(point) >= MIN_VISIBLE_LIGHT
+    }
+
+    fn add(&mut self, tile: IVec2, level: f32) {
+        if let Some(tile_level) = self.levels.get_mut(tile) {
+            *tile_level += level;
+        }
+    }
+}
+
+/// Tag component for the darkness overlay
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
+pub struct DarknessOverlay;
+
+/// Lights that have changed or moved
+type ChangedLights = (
+    With<LightSource>,
+    Or<(Changed<LightSource>, Changed<GlobalTransform>)>,
+);
+
+/// Colliders that have changed or moved, whether or not they affect sight
+type ChangedColliders = (
+    With<Collider>,
+    Or<(
+        Changed<Collider>,
+        Changed<CollisionGroups>,
+        Changed<Occluder>,
+        Changed<GlobalTransform>,
+    )>,
+);
+
+/// Everything removed since last time that used to affect the light map
+#[derive(SystemParam)]
+pub struct LightingRemovals<'w, 's> {
+    lights: RemovedComponents<'w, 's, LightSource>,
+    room_lights: RemovedComponents<'w, 's, RoomLight>,
+    colliders: RemovedComponents<'w, 's, Collider>,
+}
+
+impl LightingRemovals<'_, '_> {
+    /// Check if anything has been removed, reading every removal so it isn't seen again next time
+    fn any(&mut self) -> bool {
+        let removed = self.lights.read().count()
+            + self.room_lights.read().count()
+            + self.colliders.read().count();
+        removed > 0
+    }
+}
+
+/// Run condition for only recalculating the light map when something that affects it has changed
+///
+/// That is, when a light has moved, changed or gone out, a room's power has changed, or something
+/// that blocks sight (and so light) has moved, changed or been removed.
+pub fn lighting_changed(
+    light_qry: Query<(), ChangedLights>,
+    room_light_qry: Query<(), Changed<RoomLight>>,
+    collider_qry: Query<Option<&CollisionGroups>, ChangedColliders>,
+    mut removed: LightingRemovals,
+    ambient: Res<AmbientLightLevel>,
+    light_map: Res<LightMap>,
+) -> bool {
+    // Colliders without any groups are in all of them
+    let blocks_light = |groups: Option<&CollisionGroups>| {
+        groups.is_none_or(|groups| {
+            groups
+                .memberships
+                .intersects(OPAQUE_GROUP | TRANSLUCENT_GROUP)
+        })
+    };
+
+    removed.any()
+        || ambient.is_changed()
+        || light_map.is_changed()
+        || !light_qry.is_empty()
+        || !room_light_qry.is_empty()
+        || collider_qry.iter().any(blocks_light)
+}
+
+pub fn update_light_map(
+    rapier_context: Res<RapierContext>,
+    light_qry: Query<(Entity, &GlobalTransform, &LightSource)>,
+    room_light_qry: Query<&RoomLight>,
+    occluder_qry: Query<(&Occluder, &GlobalTransform)>,
+    ambient: Res<AmbientLightLevel>,
+    mut light_map: ResMut<LightMap>,
+) {
+    let bounds = light_map.bounds();
+    let mut levels = LightMap::new(bounds, ambient.0);
+
+    for room_light in room_light_qry.iter().filter(|light| light.powered) {
+        for y in room_light.bounds.min.y..room_light.bounds.max.y {
+            for x in room_light.bounds.min.x..room_light.bounds.max.x {
+                levels.add(IVec2::new(x, y), room_light.intensity);
+            }
+        }
+    }
+
+    for (light, light_transform, source) in light_qry.iter() {
+        let origin = light_transform.translation().truncate();
+        // Light goes wherever sight would, so we can find where it reaches the same way
+        let reach = FieldOfView::new(source.radius, source.half_angle);
+        let points = cast_view_cone(
+            light,
+            origin,
+            &reach,
+            light_transform.right().truncate(),
+            &rapier_context,
+            &occluder_qry,
+        );
+
+        for tile in tiles_in_view(&points, bounds) {
+            let distance = tile_center(tile).distance(origin);
+            let falloff = (1.0 - distance / source.radius).max(0.0);
+            levels.add(tile, source.intensity * falloff);
+        }
+    }
+
+    // Only trigger change detection if the lighting has actually changed
+    light_map.set_if_neq(levels);
+}
+
+/// Keep the light map covering the same tiles as the explored map
+pub fn resize_light_map(explored: Res<ExploredMap>, mut light_map: ResMut<LightMap>) {
+    if explored.is_changed() && explored.bounds() != light_map.bounds() {
+        *light_map = LightMap::new(explored.bounds(), 0.0);
+    }
+}
+
+pub fn setup_darkness_overlay(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    light_map: Res<LightMap>,
+) {
+    let bounds = light_map.bounds().as_rect();
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(bounds.size() * TILE_SIZE),
+                ..Default::default()
+            },
+            texture: images.add(make_darkness_image(&light_map)),
+            transform: Transform::from_translation((bounds.center() * TILE_SIZE).extend(50.0)),
+            ..Default::default()
+        },
+        // Make sure field of view cameras see the darkness too
+        RenderLayers::from_layers(&[0, 1, 2]),
+        DarknessOverlay,
+    ));
+}
+
+/// Keep the darkness overlay in sync with the light map
+pub fn update_darkness_overlay(
+    light_map: Res<LightMap>,
+    mut overlay_qry: Query<(&Handle<Image>, &mut Sprite, &mut Transform), With<DarknessOverlay>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if !light_map.is_changed() {
+        return;
+    }
+
+    let bounds = light_map.bounds().as_rect();
+    for (overlay, mut sprite, mut transform) in overlay_qry.iter_mut() {
+        if let Some(image) = images.get_mut(overlay) {
+            *image = make_darkness_image(&light_map);
+        }
+        // The map may also have been resized
+        sprite.custom_size = Some(bounds.size() * TILE_SIZE);
+        transform.translation = (bounds.center() * TILE_SIZE).extend(transform.translation.z);
+    }
+}
+
+/// Create the darkness texture from the light map, with one pixel per tile
+fn make_darkness_image(light_map: &LightMap) -> Image {
+    let bounds = light_map.bounds();
+    let size = Extent3d {
+        width: bounds.width() as u32,
+        height: bounds.height() as u32,
+        ..Default::default()
+    };
+
+    // Flip y: In Bevy space, y points up; in texture space, y points down!
+    let data = (bounds.min.y..bounds.max.y)
+        .rev()
+        .flat_map(|y| (bounds.min.x..bounds.max.x).map(move |x| IVec2::new(x, y)))
+        .flat_map(|tile| {
+            let darkness = (1.0 - light_map.level(tile)) * MAX_DARKNESS;
+            [0, 0, 0, (darkness * 255.0) as u8]
+        })
+        .collect();
+
+    Image::new(
+        size,
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
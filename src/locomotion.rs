@@ -0,0 +1,119 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+/// How something moves and turns, whether it's controlled by the player or by AI
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct Locomotion {
+    /// Top speed while walking, in pixels per second
+    pub walk_speed: f32,
+    /// Top speed while sneaking, in pixels per second
+    pub sneak_speed: f32,
+    /// How quickly speed builds up toward the top speed, in pixels per second squared
+    pub acceleration: f32,
+    /// How quickly speed falls away when not trying to move, in pixels per second squared
+    pub friction: f32,
+    /// How quickly facing can turn, in radians per second
+    pub turn_rate: f32,
+    velocity: Vec2,
+}
+
+impl Default for Locomotion {
+    fn default() -> Self {
+        Self::new(150.0, 30.0)
+    }
+}
+
+impl Locomotion {
+    pub fn new(walk_speed: f32, sneak_speed: f32) -> Self {
+        Self {
+            walk_speed,
+            sneak_speed,
+            acceleration: 1200.0,
+            friction: 1500.0,
+            turn_rate: TAU * 2.0,
+            velocity: Vec2::ZERO,
+        }
+    }
+
+    pub fn with_acceleration(mut self, acceleration: f32, friction: f32) -> Self {
+        self.acceleration = acceleration;
+        self.friction = friction;
+        self
+    }
+
+    pub fn with_turn_rate(mut self, turn_rate: f32) -> Self {
+        self.turn_rate = turn_rate;
+        self
+    }
+
+    /// How fast, and which way, this is currently moving
+    pub fn velocity(&self) -> Vec2 {
+        self.velocity
+    }
+}
+
+/// Where something with `Locomotion` is trying to go and look
+///
+/// Player input or AI sets this each frame; `apply_locomotion` does the actual moving and turning.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Component)]
+pub struct MoveIntent {
+    /// Which way to move; anything shorter than a unit vector moves slower than top speed
+    pub direction: Vec2,
+    pub sneaking: bool,
+    /// Which way to turn to face, if anywhere
+    pub facing: Option<Vec2>,
+}
+
+/// Move and turn everything with `Locomotion` according to its `MoveIntent`
+///
+/// Anything with a `KinematicCharacterController` is moved through it, so that it collides with
+/// things; anything else is moved directly.
+pub fn apply_locomotion(
+    time: Res<Time>,
+    mut mover_qry: Query<(
+        &mut Locomotion,
+        &MoveIntent,
+        &mut Transform,
+        Option<&mut KinematicCharacterController>,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    for (mut locomotion, intent, mut transform, controller) in mover_qry.iter_mut() {
+        let top_speed = if intent.sneaking {
+            locomotion.sneak_speed
+        } else {
+            locomotion.walk_speed
+        };
+        let target = intent.direction.clamp_length_max(1.0) * top_speed;
+        let rate = if target == Vec2::ZERO {
+            locomotion.friction
+        } else {
+            locomotion.acceleration
+        };
+        let change = (target - locomotion.velocity).clamp_length_max(rate * dt);
+        locomotion.velocity += change;
+
+        let translation = locomotion.velocity * dt;
+        if translation.length_squared() > f32::EPSILON {
+            match controller {
+                // Use get_or_insert and then add our translation
+                // This preserves any forces acting on the controller added by other systems
+                Some(mut controller) => {
+                    *controller.translation.get_or_insert(Vec2::ZERO) += translation;
+                }
+                None => transform.translation += translation.extend(0.0),
+            }
+        }
+
+        if let Some(to) = intent.facing {
+            let facing = transform.local_x().truncate();
+            let angle = facing.angle_between(to);
+            if angle.abs() > f32::EPSILON {
+                let max_turn = locomotion.turn_rate * dt;
+                transform.rotate_z(angle.clamp(-max_turn, max_turn));
+            }
+        }
+    }
+}
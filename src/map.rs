@@ -1,13 +1,98 @@
 use bevy::prelude::*;
 use itertools::Itertools;
 use petgraph::{algo::min_spanning_tree, data::FromElements, prelude::UnGraphMap};
+use serde::{Deserialize, Serialize};
 
 use crate::rand::*;
 
 pub const TILE_SIZE: f32 = 16.0;
 const TILE_Z: f32 = 1.0;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource)]
+/// The tile that contains the world-space `point`
+pub fn tile_at(point: Vec2) -> IVec2 {
+    (point / TILE_SIZE).floor().as_ivec2()
+}
+
+/// The world-space center of `tile`
+pub fn tile_center(tile: IVec2) -> Vec2 {
+    (tile.as_vec2() + Vec2::splat(0.5)) * TILE_SIZE
+}
+
+/// A value for every tile within some bounds, e.g. whether or not it's been explored
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TileGrid<T> {
+    /// Tiles covered by this grid; `min` is inclusive, `max` is exclusive
+    bounds: IRect,
+    tiles: Vec<T>,
+}
+
+impl<T: Clone> TileGrid<T> {
+    /// Create a new grid covering `bounds`, with every tile set to `value`
+    pub fn new(bounds: IRect, value: T) -> Self {
+        let size = bounds.size();
+
+        Self {
+            bounds,
+            tiles: vec![value; (size.x * size.y) as usize],
+        }
+    }
+}
+
+impl<T> TileGrid<T> {
+    /// The tiles covered by this grid
+    pub fn bounds(&self) -> IRect {
+        self.bounds
+    }
+
+    /// Where `tile` is stored within this grid, if it's within bounds
+    pub fn index(&self, tile: IVec2) -> Option<usize> {
+        let IRect { min, max } = self.bounds;
+        if tile.x < min.x || tile.y < min.y || tile.x >= max.x || tile.y >= max.y {
+            return None;
+        }
+        let offset = tile - min;
+
+        Some((offset.y * self.bounds.width() + offset.x) as usize)
+    }
+
+    /// The tile stored at `idx`; the inverse of `index`
+    pub fn tile_of(&self, idx: usize) -> IVec2 {
+        let width = self.bounds.width();
+        self.bounds.min + IVec2::new(idx as i32 % width, idx as i32 / width)
+    }
+
+    pub fn get(&self, tile: IVec2) -> Option<&T> {
+        self.index(tile).map(|idx| &self.tiles[idx])
+    }
+
+    pub fn get_mut(&mut self, tile: IVec2) -> Option<&mut T> {
+        self.index(tile).map(|idx| &mut self.tiles[idx])
+    }
+
+    /// Every tile, in the order they're stored
+    pub fn iter(&self) -> impl Iterator<Item = (IVec2, &T)> {
+        self.tiles
+            .iter()
+            .enumerate()
+            .map(|(idx, value)| (self.tile_of(idx), value))
+    }
+}
+
+impl<T> std::ops::Index<usize> for TileGrid<T> {
+    type Output = T;
+
+    fn index(&self, idx: usize) -> &T {
+        &self.tiles[idx]
+    }
+}
+
+impl<T> std::ops::IndexMut<usize> for TileGrid<T> {
+    fn index_mut(&mut self, idx: usize) -> &mut T {
+        &mut self.tiles[idx]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource, Serialize, Deserialize)]
 pub struct ShipParameters {
     pub seed: Option<u64>,
     pub ship_length: i32,
@@ -36,6 +121,52 @@ impl Default for ShipParameters {
     }
 }
 
+/// Ready-made ship parameters, for picking a ship's size without tuning every parameter
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShipPreset {
+    Small,
+    #[default]
+    Standard,
+    Large,
+}
+
+impl ShipPreset {
+    pub const ALL: [Self; 3] = [Self::Small, Self::Standard, Self::Large];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Small => "Small",
+            Self::Standard => "Standard",
+            Self::Large => "Large",
+        }
+    }
+
+    /// Parameters for a ship of this size, with no seed
+    pub fn parameters(self) -> ShipParameters {
+        match self {
+            Self::Small => ShipParameters {
+                ship_length: 40,
+                max_width: 16,
+                min_rooms: 6,
+                max_rooms: 14,
+                room_width_max: 12,
+                room_height_max: 12,
+                ..Default::default()
+            },
+            Self::Standard => ShipParameters::default(),
+            Self::Large => ShipParameters {
+                ship_length: 96,
+                max_width: 32,
+                min_rooms: 20,
+                max_rooms: 40,
+                room_width_max: 20,
+                room_height_max: 20,
+                ..Default::default()
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 enum EdgeWeight {
     Adjacent,
@@ -49,6 +180,78 @@ pub struct Rooms {
     mst: UnGraphMap<usize, EdgeWeight>,
 }
 impl Rooms {
+    /// Lay out `rooms`, working out which are adjacent and how they're all connected
+    pub fn new(rooms: Vec<IRect>) -> Self {
+        let mut graph = UnGraphMap::new();
+        for idx in 0..rooms.len() {
+            graph.add_node(idx);
+        }
+        let mut rooms = Self {
+            rooms,
+            graph,
+            mst: UnGraphMap::new(),
+        };
+
+        // Calculate Delauney triangulation of the rooms
+        let points = rooms
+            .iter()
+            .map(|room| {
+                let center = room.as_rect().center().as_dvec2();
+                delaunator::Point {
+                    x: center.x,
+                    y: center.y,
+                }
+            })
+            .collect_vec();
+        let triangulation = delaunator::triangulate(&points);
+
+        // This is adapted from `forEachTriangleEdge` function at <https://mapbox.github.io/delaunator/>
+        // Kudos to "1L-1UX" (illiux#5291) on Roguelikes Discord - Thank you!
+        for e in 0..triangulation.triangles.len() {
+            let o = triangulation.halfedges[e];
+            if e > o || o == delaunator::EMPTY {
+                let p = triangulation.triangles[e];
+                let q = triangulation.triangles[delaunator::next_halfedge(e)];
+
+                // Weight the links by how far from the spine they are
+                // "How far" being the average of the absolute value of their respective y endpoints
+                let y = (rooms.rooms[p].center().as_vec2().y.abs()
+                    + rooms.rooms[q].center().as_vec2().y.abs())
+                    / 2.0;
+                // Additionally favor shorter paths
+                let d = rooms.rooms[p]
+                    .center()
+                    .as_vec2()
+                    .distance(rooms.rooms[q].center().as_vec2());
+
+                rooms.add_edge(p, q, EdgeWeight::Weighted(y, d));
+            }
+        }
+
+        // Find adjacent rooms
+        let raw_room_list = rooms.rooms.clone();
+        for (idx, room) in raw_room_list.iter().enumerate() {
+            let adjacency = room.inset(1);
+            for (other_idx, other_room) in raw_room_list.iter().enumerate() {
+                if other_idx <= idx {
+                    continue;
+                }
+                let size = adjacency.intersect(*other_room).size();
+                let area = size.x * size.y;
+                // If we touch only on a corner, the intersection has area 1 - but we don't care about that
+                if area > 1 {
+                    // Set the weight for this edge to signify adjacency
+                    rooms.add_edge(idx, other_idx, EdgeWeight::Adjacent);
+                }
+            }
+        }
+
+        // Calculate MST
+        rooms.mst = UnGraphMap::from_elements(min_spanning_tree(&rooms.graph));
+
+        rooms
+    }
+
     pub fn len(&self) -> usize {
         self.rooms.len()
     }
@@ -57,9 +260,45 @@ impl Rooms {
         self.rooms.is_empty()
     }
 
-    fn push(&mut self, new_room: IRect) {
-        self.rooms.push(new_room);
-        self.graph.add_node(self.rooms.len() - 1);
+    /// The smallest rectangle containing every room
+    pub fn bounds(&self) -> IRect {
+        self.rooms
+            .iter()
+            .copied()
+            .reduce(|bounds, room| bounds.union(room))
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&IRect> {
+        self.rooms.get(idx)
+    }
+
+    /// The room containing `tile`, if any
+    pub fn room_at(&self, tile: IVec2) -> Option<usize> {
+        self.rooms.iter().position(|room| {
+            tile.x >= room.min.x
+                && tile.y >= room.min.y
+                && tile.x < room.max.x
+                && tile.y < room.max.y
+        })
+    }
+
+    /// Rooms connected to `idx` in the ship's layout
+    pub fn connected(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        self.mst.neighbors(idx)
+    }
+
+    /// Where rooms connected in the ship's layout share a wall, and a door can join them
+    ///
+    /// Each doorway is the pair of rooms it joins, and the tiles either side of their shared wall.
+    pub fn doorways(&self) -> impl Iterator<Item = (usize, usize, IRect)> + '_ {
+        self.mst
+            .all_edges()
+            .filter(|(_, _, &weight)| weight == EdgeWeight::Adjacent)
+            .map(|(a, b, _)| {
+                let shared = self.rooms[a].inset(1).intersect(self.rooms[b].inset(1));
+                (a, b, shared)
+            })
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &IRect> {
@@ -72,7 +311,7 @@ impl Rooms {
 }
 
 pub fn setup_map(mut commands: Commands, mut ship: ResMut<ShipParameters>) {
-    let mut rooms = Rooms::default();
+    let mut rooms = Vec::<IRect>::new();
 
     // Enforce the rule that min_rooms *must* be less than max_rooms
     // NB: We assume the max_rooms parameter was set accurately and make no attempt to adjust it
@@ -148,62 +387,7 @@ pub fn setup_map(mut commands: Commands, mut ship: ResMut<ShipParameters>) {
         return setup_map(commands, ship);
     }
 
-    // Calculate Delauney triangulation of the rooms
-    let points = rooms
-        .iter()
-        .map(|room| {
-            let center = room.as_rect().center().as_dvec2();
-            delaunator::Point {
-                x: center.x,
-                y: center.y,
-            }
-        })
-        .collect_vec();
-    let triangulation = delaunator::triangulate(&points);
-
-    // This is adapted from `forEachTriangleEdge` function at <https://mapbox.github.io/delaunator/>
-    // Kudos to "1L-1UX" (illiux#5291) on Roguelikes Discord - Thank you!
-    for e in 0..triangulation.triangles.len() {
-        let o = triangulation.halfedges[e];
-        if e > o || o == delaunator::EMPTY {
-            let p = triangulation.triangles[e];
-            let q = triangulation.triangles[delaunator::next_halfedge(e)];
-
-            // Weight the links by how far from the spine they are
-            // "How far" being the average of the absolute value of their respective y endpoints
-            let y = (rooms.rooms[p].center().as_vec2().y.abs()
-                + rooms.rooms[q].center().as_vec2().y.abs())
-                / 2.0;
-            // Additionally favor shorter paths
-            let d = rooms.rooms[p]
-                .center()
-                .as_vec2()
-                .distance(rooms.rooms[q].center().as_vec2());
-
-            rooms.add_edge(p, q, EdgeWeight::Weighted(y, d));
-        }
-    }
-
-    // Find adjacent rooms
-    let raw_room_list = rooms.rooms.clone();
-    for (idx, room) in raw_room_list.iter().enumerate() {
-        let adjacency = room.inset(1);
-        for (other_idx, other_room) in raw_room_list.iter().enumerate() {
-            if other_idx <= idx {
-                continue;
-            }
-            let size = adjacency.intersect(*other_room).size();
-            let area = size.x * size.y;
-            // If we touch only on a corner, the intersection has area 1 - but we don't care about that
-            if area > 1 {
-                // Set the weight for this edge to signify adjacency
-                rooms.add_edge(idx, other_idx, EdgeWeight::Adjacent);
-            }
-        }
-    }
-
-    // Calculate MST
-    rooms.mst = UnGraphMap::from_elements(min_spanning_tree(&rooms.graph));
+    let rooms = Rooms::new(rooms);
 
     // Spawn rooms
     for room in rooms.iter() {
@@ -0,0 +1,141 @@
+use std::path::Path;
+
+use bevy::{app::AppExit, prelude::*};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    core::GameState,
+    map::{ShipParameters, ShipPreset},
+    rand::*,
+    save::{PendingLoad, SaveGame, SAVE_PATH},
+};
+
+/// Width of the buttons in every menu, in (egui) points
+const BUTTON_WIDTH: f32 = 160.0;
+
+/// The choices made in the main menu for the next new game
+#[derive(Debug, Default, Clone, PartialEq, Eq, Resource)]
+pub struct NewGameSettings {
+    /// The ship's seed as typed in; a number is used as-is, anything else is hashed into one, and
+    /// nothing at all picks a random seed
+    pub seed: String,
+    pub preset: ShipPreset,
+}
+
+impl NewGameSettings {
+    /// Parameters for the ship these settings describe
+    pub fn ship_parameters(&self) -> ShipParameters {
+        let seed = self.seed.trim();
+        let seed = if seed.is_empty() {
+            None
+        } else {
+            Some(seed.parse().unwrap_or_else(|_| seed_rng(seed).gen()))
+        };
+
+        ShipParameters {
+            seed,
+            ..self.preset.parameters()
+        }
+    }
+}
+
+/// A menu window, centered on the screen
+fn menu_window(title: &str) -> egui::Window<'static> {
+    egui::Window::new(title)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .collapsible(false)
+        .resizable(false)
+}
+
+fn menu_button(ui: &mut egui::Ui, text: &str) -> egui::Response {
+    ui.add_sized([BUTTON_WIDTH, 0.0], egui::Button::new(text))
+}
+
+pub fn loading_screen(mut contexts: EguiContexts) {
+    menu_window("Loading").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label("Loading...");
+        });
+    });
+}
+
+pub fn main_menu(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut settings: ResMut<NewGameSettings>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut exits: EventWriter<AppExit>,
+) {
+    let can_continue = Path::new(SAVE_PATH).exists();
+
+    menu_window("Main Menu").show(contexts.ctx_mut(), |ui| {
+        ui.vertical_centered(|ui| {
+            if menu_button(ui, "New Game").clicked() {
+                commands.insert_resource(settings.ship_parameters());
+                next_state.set(GameState::InGame);
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Seed:");
+                ui.add(egui::TextEdit::singleline(&mut settings.seed).hint_text("Random"));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Ship:");
+                for preset in ShipPreset::ALL {
+                    ui.selectable_value(&mut settings.preset, preset, preset.name());
+                }
+            });
+
+            ui.separator();
+            if ui
+                .add_enabled_ui(can_continue, |ui| menu_button(ui, "Continue"))
+                .inner
+                .clicked()
+            {
+                match SaveGame::load(SAVE_PATH) {
+                    Ok(save) => {
+                        commands.insert_resource(save.ship);
+                        commands.insert_resource(PendingLoad(save));
+                        next_state.set(GameState::InGame);
+                    }
+                    Err(err) => warn!("Could not load game from {SAVE_PATH}: {err}"),
+                }
+            }
+            if menu_button(ui, "Quit").clicked() {
+                exits.send(AppExit);
+            }
+        });
+    });
+}
+
+pub fn pause_menu(
+    mut contexts: EguiContexts,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut exits: EventWriter<AppExit>,
+) {
+    menu_window("Paused").show(contexts.ctx_mut(), |ui| {
+        ui.vertical_centered(|ui| {
+            if menu_button(ui, "Resume").clicked() {
+                next_state.set(GameState::InGame);
+            }
+            if menu_button(ui, "Main Menu").clicked() {
+                next_state.set(GameState::MainMenu);
+            }
+            if menu_button(ui, "Quit").clicked() {
+                exits.send(AppExit);
+            }
+        });
+    });
+}
+
+pub fn game_over_menu(mut contexts: EguiContexts, mut next_state: ResMut<NextState<GameState>>) {
+    menu_window("Game Over").show(contexts.ctx_mut(), |ui| {
+        ui.vertical_centered(|ui| {
+            ui.label("You died.");
+            if menu_button(ui, "Main Menu").clicked() {
+                next_state.set(GameState::MainMenu);
+            }
+        });
+    });
+}
@@ -0,0 +1,373 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, VecDeque},
+};
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    core::OPAQUE_GROUP,
+    door::DoorChanged,
+    locomotion::MoveIntent,
+    map::{tile_at, tile_center, TILE_SIZE},
+};
+
+/// The most tiles a path search will look at before giving up
+const MAX_SEARCH_TILES: usize = 4096;
+
+/// How close to a point on its path something must get before heading for the next one
+const ARRIVE_DISTANCE: f32 = 4.0;
+
+/// Places something is walking to in turn, and the path it's taking to the next one
+#[derive(Debug, Default, Clone, PartialEq, Component)]
+pub struct Waypoints {
+    queue: VecDeque<Vec2>,
+    /// The path to the first waypoint in the queue, found as we need it
+    path: VecDeque<Vec2>,
+}
+
+impl Waypoints {
+    /// Drop every waypoint, and walk straight to `destination` instead
+    pub fn go_to(&mut self, destination: Vec2) {
+        self.clear();
+        self.queue.push_back(destination);
+    }
+
+    /// Walk to `destination` after reaching every other waypoint
+    pub fn queue(&mut self, destination: Vec2) {
+        self.queue.push_back(destination);
+    }
+
+    /// Stop walking anywhere
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.path.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Forget the path to the next waypoint, so that a new one is found
+    pub fn invalidate_path(&mut self) {
+        self.path.clear();
+    }
+
+    /// The path to the next waypoint, followed by every waypoint after that
+    pub fn iter(&self) -> impl Iterator<Item = Vec2> + '_ {
+        self.path.iter().chain(self.queue.iter().skip(1)).copied()
+    }
+}
+
+/// A tile waiting to be visited while searching for a path
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Frontier {
+    /// Cost so far, plus the estimated cost of the rest of the way
+    estimate: f32,
+    cost: f32,
+    tile: IVec2,
+}
+
+impl Eq for Frontier {}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed, so that our BinaryHeap pops the most promising tile first
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Estimated cost between two tiles, moving in 8 directions
+fn octile_distance(a: IVec2, b: IVec2) -> f32 {
+    let delta = (a - b).abs();
+    let (long, short) = (delta.max_element() as f32, delta.min_element() as f32);
+    long + short * (std::f32::consts::SQRT_2 - 1.0)
+}
+
+/// Find a path across the tile grid from `start` to `goal`, going around anything opaque
+///
+/// Returns the world-space points to walk through in turn, ending at `goal`, or `None` if there's
+/// no way there (or it's too far away to find one).
+pub fn find_path(rapier_context: &RapierContext, start: Vec2, goal: Vec2) -> Option<Vec<Vec2>> {
+    let filter = QueryFilter::new()
+        .exclude_sensors()
+        .groups(CollisionGroups::new(Group::all(), OPAQUE_GROUP));
+    // Slightly smaller than a tile, so that anything merely touching a tile doesn't block it
+    let probe = Collider::cuboid(TILE_SIZE / 2.0 - 1.0, TILE_SIZE / 2.0 - 1.0);
+
+    // Whether a tile is blocked, looked up as we need it
+    let mut blocked = HashMap::new();
+    let mut is_blocked = |tile: IVec2| {
+        *blocked.entry(tile).or_insert_with(|| {
+            rapier_context
+                .intersection_with_shape(tile_center(tile), 0.0, &probe, filter)
+                .is_some()
+        })
+    };
+
+    let start_tile = tile_at(start);
+    let goal_tile = tile_at(goal);
+    if is_blocked(goal_tile) {
+        return None;
+    }
+
+    let mut came_from = HashMap::new();
+    let mut costs = HashMap::from([(start_tile, 0.0)]);
+    let mut frontier = BinaryHeap::from([Frontier {
+        estimate: octile_distance(start_tile, goal_tile),
+        cost: 0.0,
+        tile: start_tile,
+    }]);
+
+    let mut searched = 0;
+    while let Some(Frontier { cost, tile, .. }) = frontier.pop() {
+        if tile == goal_tile {
+            break;
+        }
+        if costs.get(&tile).map(|&best| best < cost).unwrap_or(false) {
+            // We've already found a quicker way here
+            continue;
+        }
+        searched += 1;
+        if searched > MAX_SEARCH_TILES {
+            return None;
+        }
+
+        for y in -1..=1 {
+            for x in -1..=1 {
+                let step = IVec2::new(x, y);
+                if step == IVec2::ZERO {
+                    continue;
+                }
+                let next = tile + step;
+                if is_blocked(next) {
+                    continue;
+                }
+                // Don't cut corners around obstacles when moving diagonally
+                if x != 0
+                    && y != 0
+                    && (is_blocked(tile + IVec2::new(x, 0)) || is_blocked(tile + IVec2::new(0, y)))
+                {
+                    continue;
+                }
+
+                let next_cost = cost + step.as_vec2().length();
+                if costs
+                    .get(&next)
+                    .map(|&best| best <= next_cost)
+                    .unwrap_or(false)
+                {
+                    continue;
+                }
+                costs.insert(next, next_cost);
+                came_from.insert(next, tile);
+                frontier.push(Frontier {
+                    estimate: next_cost + octile_distance(next, goal_tile),
+                    cost: next_cost,
+                    tile: next,
+                });
+            }
+        }
+    }
+
+    if start_tile != goal_tile && !came_from.contains_key(&goal_tile) {
+        return None;
+    }
+
+    // Walk back from the goal, keeping only the tiles where the path turns
+    let mut path = vec![goal];
+    let mut tile = goal_tile;
+    let mut heading = None;
+    while let Some(&previous) = came_from.get(&tile) {
+        let step = tile - previous;
+        if heading.is_some() && heading != Some(step) {
+            path.push(tile_center(tile));
+        }
+        heading = Some(step);
+        tile = previous;
+    }
+    path.reverse();
+
+    Some(path)
+}
+
+/// Walk everything with `Waypoints` along its path to each waypoint in turn
+pub fn follow_waypoints(
+    rapier_context: Res<RapierContext>,
+    mut walker_qry: Query<(&mut Waypoints, &mut MoveIntent, &GlobalTransform)>,
+) {
+    for (mut waypoints, mut intent, transform) in walker_qry.iter_mut() {
+        // Anything not already walking somewhere is being moved by something else, e.g. the player
+        // having just taken over, so leave its intent alone
+        if waypoints.is_empty() {
+            continue;
+        }
+        let position = transform.translation().truncate();
+
+        while let Some(&destination) = waypoints.queue.front() {
+            if position.distance(destination) <= ARRIVE_DISTANCE {
+                waypoints.queue.pop_front();
+                waypoints.path.clear();
+                continue;
+            }
+            if waypoints.path.is_empty() {
+                match find_path(&rapier_context, position, destination) {
+                    Some(path) => waypoints.path = path.into(),
+                    None => {
+                        debug!("No path from {position} to {destination}");
+                        waypoints.queue.pop_front();
+                        continue;
+                    }
+                }
+            }
+            break;
+        }
+
+        while let Some(&next) = waypoints.path.front() {
+            if waypoints.path.len() > 1 && position.distance(next) <= ARRIVE_DISTANCE {
+                waypoints.path.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        if let Some(&next) = waypoints.path.front() {
+            intent.direction = (next - position).normalize_or_zero();
+        } else {
+            // We've just arrived, or given up
+            intent.direction = Vec2::ZERO;
+        }
+    }
+}
+
+/// Find new paths for anything walking through a door that's opened or closed
+pub fn invalidate_paths(
+    mut changes: EventReader<DoorChanged>,
+    mut waypoints_qry: Query<&mut Waypoints>,
+) {
+    if changes.read().count() == 0 {
+        return;
+    }
+
+    for mut waypoints in waypoints_qry.iter_mut() {
+        waypoints.invalidate_path();
+    }
+}
+
+pub fn draw_waypoints(walker_qry: Query<(&Waypoints, &GlobalTransform)>, mut gizmos: Gizmos) {
+    for (waypoints, transform) in walker_qry.iter() {
+        if waypoints.is_empty() {
+            continue;
+        }
+        let start = transform.translation().truncate();
+        gizmos.linestrip_2d(
+            std::iter::once(start).chain(waypoints.iter()),
+            Color::YELLOW.with_a(0.5),
+        );
+        for waypoint in waypoints.queue.iter() {
+            gizmos.circle_2d(*waypoint, 4.0, Color::YELLOW.with_a(0.75));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::SQRT_2;
+
+    use super::*;
+
+    /// A headless world with an opaque wall over each inclusive rect of tiles in `walls`
+    fn world(walls: &[(IVec2, IVec2)]) -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            RapierPhysicsPlugin::<()>::pixels_per_meter(32.0),
+        ));
+        for &(min, max) in walls {
+            let center = (tile_center(min) + tile_center(max)) / 2.0;
+            let half_size = ((max - min).as_vec2() + Vec2::ONE) * TILE_SIZE / 2.0;
+            app.world.spawn((
+                TransformBundle::from_transform(Transform::from_translation(center.extend(0.0))),
+                Collider::cuboid(half_size.x, half_size.y),
+                CollisionGroups::new(OPAQUE_GROUP, Group::all()),
+            ));
+        }
+
+        // Let Rapier pick up all our colliders
+        app.update();
+        app.update();
+
+        app
+    }
+
+    #[test]
+    fn octile_distance_moves_diagonally_then_straight() {
+        assert_eq!(octile_distance(IVec2::ZERO, IVec2::ZERO), 0.0);
+        assert_eq!(octile_distance(IVec2::ZERO, IVec2::new(3, 0)), 3.0);
+        assert_eq!(octile_distance(IVec2::ZERO, IVec2::new(0, -3)), 3.0);
+        assert!((octile_distance(IVec2::ZERO, IVec2::new(2, 2)) - 2.0 * SQRT_2).abs() < 1e-5);
+
+        let distance = octile_distance(IVec2::new(-1, 2), IVec2::new(2, 1));
+        assert!((distance - (2.0 + SQRT_2)).abs() < 1e-5);
+        assert_eq!(
+            distance,
+            octile_distance(IVec2::new(2, 1), IVec2::new(-1, 2))
+        );
+    }
+
+    #[test]
+    fn paths_go_around_walls() {
+        let app = world(&[(IVec2::new(3, -3), IVec2::new(3, 3))]);
+        let rapier_context = app.world.resource::<RapierContext>();
+        let start = tile_center(IVec2::ZERO);
+        let goal = tile_center(IVec2::new(6, 0));
+
+        let path = find_path(rapier_context, start, goal).unwrap();
+        assert_eq!(path.last(), Some(&goal));
+
+        // Every leg of the path is clear of the wall
+        let filter = QueryFilter::new().groups(CollisionGroups::new(Group::all(), OPAQUE_GROUP));
+        let mut from = start;
+        for &to in path.iter() {
+            assert!(
+                rapier_context
+                    .cast_ray(from, to - from, 1.0, true, filter)
+                    .is_none(),
+                "{from} to {to} goes through the wall"
+            );
+            from = to;
+        }
+    }
+
+    #[test]
+    fn unreachable_goals_have_no_path() {
+        // A goal walled in on every side
+        let app = world(&[
+            (IVec2::new(18, -2), IVec2::new(18, 2)),
+            (IVec2::new(22, -2), IVec2::new(22, 2)),
+            (IVec2::new(19, 2), IVec2::new(21, 2)),
+            (IVec2::new(19, -2), IVec2::new(21, -2)),
+        ]);
+        let rapier_context = app.world.resource::<RapierContext>();
+        let start = tile_center(IVec2::ZERO);
+
+        assert_eq!(
+            find_path(rapier_context, start, tile_center(IVec2::new(20, 0))),
+            None
+        );
+        // Nor is there a path into a wall
+        assert_eq!(
+            find_path(rapier_context, start, tile_center(IVec2::new(18, 0))),
+            None
+        );
+    }
+}
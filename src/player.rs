@@ -5,13 +5,36 @@ use bevy_rapier2d::prelude::*;
 
 use crate::{
     camera::{Follow, MainCamera},
+    combat::{FireMode, Weapon},
     core::PLAYER_GROUP,
-    fov::FieldOfView,
+    fov::{FieldOfView, RayResolution, ViewCone, Viewable},
+    health::{Damage, DamageKind, Health, OnDeath},
+    hearing::{Hearing, Noise},
+    input::{Action, ActionState, LastInputDevice},
+    interaction::INTERACT_ARC,
+    inventory::Inventory,
+    locomotion::{Locomotion, MoveIntent},
+    map::{Rooms, TILE_SIZE},
+    pathfinding::Waypoints,
+    ship_systems::Breathes,
     sprites::Sprites,
 };
 
-//FIXME: This should be a component on the player
-const PLAYER_MOVE_SPEED: f32 = 150.0;
+/// How far the aiming stick must be pushed before the player turns to face it
+const AIM_DEADZONE: f32 = 0.25;
+
+/// How far the player's footsteps can be heard, walking and sneaking
+const FOOTSTEP_LOUDNESS: f32 = 160.0;
+const SNEAKING_FOOTSTEP_LOUDNESS: f32 = 32.0;
+
+#[derive(Debug, Deref, DerefMut)]
+pub struct FootstepTimer(Timer);
+
+impl Default for FootstepTimer {
+    fn default() -> Self {
+        Self(Timer::from_seconds(0.4, TimerMode::Repeating))
+    }
+}
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
 pub struct Player;
@@ -19,13 +42,25 @@ pub struct Player;
 pub fn spawn_player(
     mut commands: Commands,
     sprites: Res<Sprites>,
+    rooms: Option<Res<Rooms>>,
     camera_qry: Query<Entity, With<MainCamera>>,
 ) {
+    // Start in the room nearest the middle of the ship, so there's air to breathe
+    let start = rooms
+        .and_then(|rooms| {
+            rooms
+                .iter()
+                .min_by_key(|room| room.center().length_squared())
+                .copied()
+        })
+        .map(|room| room.as_rect().center() * TILE_SIZE)
+        .unwrap_or(Vec2::ZERO);
+
     let player_entity = commands
         .spawn((
             SpriteBundle {
                 texture: sprites.player.clone(),
-                transform: Transform::from_xyz(0.0, 0.0, 5.0),
+                transform: Transform::from_translation(start.extend(5.0)),
                 ..Default::default()
             },
             Collider::capsule(Vec2::new(0.0, -5.0), Vec2::new(0.0, 5.0), 12.0),
@@ -34,9 +69,27 @@ pub fn spawn_player(
                 ..Default::default()
             },
             Velocity::default(),
+            (
+                Locomotion::default(),
+                MoveIntent::default(),
+                Waypoints::default(),
+            ),
+            (Health::new(100.0), OnDeath::LeaveCorpse, Breathes),
+            Hearing::default(),
+            Inventory::new(20.0),
+            Weapon::new(
+                FireMode::Hitscan { range: 320.0 },
+                Damage::new(10.0, DamageKind::Energy),
+                0.25,
+            ),
+            // Drones need to see us, but we never need hiding from ourselves
+            Viewable::Always,
             Player,
             CollisionGroups::new(PLAYER_GROUP, Group::all()),
-            FieldOfView::new(256.0, TAU / 12.0),
+            FieldOfView::new(256.0, TAU / 12.0)
+                // Peripheral vision, so we're aware of what's right around us
+                .with_cone(ViewCone::around(32.0))
+                .with_resolution(RayResolution::ArcLength(4.0)),
         ))
         .id();
     // Make sure the game's camera follows the player
@@ -53,7 +106,7 @@ pub fn player_debug(player_qry: Query<&GlobalTransform, With<Player>>, mut gizmo
         gizmos.arc_2d(
             position,
             looking.angle_between(Vec2::Y),
-            TAU / 3.0,
+            INTERACT_ARC,
             20.0,
             Color::GREEN.with_a(0.5),
         );
@@ -66,58 +119,124 @@ pub fn player_debug(player_qry: Query<&GlobalTransform, With<Player>>, mut gizmo
 }
 
 pub fn player_walk(
-    time: Res<Time>,
-    keys: Res<Input<KeyCode>>,
-    mut player_qry: Query<&mut KinematicCharacterController, With<Player>>,
+    actions: Res<ActionState>,
+    mut player_qry: Query<(&mut MoveIntent, &mut Waypoints), With<Player>>,
 ) {
-    if let Ok(mut player_velocity) = player_qry.get_single_mut() {
-        let mut velocity = Vec2::ZERO;
+    if let Ok((mut intent, mut waypoints)) = player_qry.get_single_mut() {
+        intent.direction = Vec2::new(
+            actions.axis(Action::MoveLeft, Action::MoveRight),
+            actions.axis(Action::MoveDown, Action::MoveUp),
+        );
+        intent.sneaking = actions.pressed(Action::Sneak);
 
-        if keys.pressed(KeyCode::W) {
-            velocity += Vec2::Y;
-        }
-        if keys.pressed(KeyCode::A) {
-            velocity += Vec2::NEG_X;
-        }
-        if keys.pressed(KeyCode::S) {
-            velocity += Vec2::NEG_Y;
-        }
-        if keys.pressed(KeyCode::D) {
-            velocity += Vec2::X;
+        // Walking ourselves takes over from walking to wherever we clicked
+        if intent.direction != Vec2::ZERO && !waypoints.is_empty() {
+            waypoints.clear();
         }
+    }
+}
 
-        velocity = velocity.normalize_or_zero() * PLAYER_MOVE_SPEED * time.delta_seconds();
-        if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
-            velocity *= 0.2;
-        }
+/// Walk to wherever the player clicks, or queue it up to walk to next
+pub fn player_click_to_move(
+    actions: Res<ActionState>,
+    mut player_qry: Query<&mut Waypoints, With<Player>>,
+    window_qry: Query<&Window, With<PrimaryWindow>>,
+    camera_qry: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) {
+    if !actions.just_pressed(Action::MoveTo) {
+        return;
+    }
 
-        if velocity.length_squared() > f32::EPSILON {
-            // Use get_or_insert and then add our velocity
-            // This preserves any forces acting on the player added by other systems
-            *player_velocity.translation.get_or_insert(Vec2::ZERO) += velocity;
+    let Some(destination) = cursor_world_position(&window_qry, &camera_qry) else {
+        return;
+    };
+    if let Ok(mut waypoints) = player_qry.get_single_mut() {
+        if actions.pressed(Action::QueueMove) {
+            waypoints.queue(destination);
+        } else {
+            waypoints.go_to(destination);
         }
     }
 }
 
+/// Where the mouse cursor is in the world, if it's over the window
+fn cursor_world_position(
+    window_qry: &Query<&Window, With<PrimaryWindow>>,
+    camera_qry: &Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) -> Option<Vec2> {
+    let cursor = window_qry.get_single().ok()?.cursor_position()?;
+    let (camera, camera_transform) = camera_qry.get_single().ok()?;
+    camera.viewport_to_world_2d(camera_transform, cursor)
+}
+
+pub fn player_footsteps(
+    time: Res<Time>,
+    actions: Res<ActionState>,
+    player_qry: Query<(Entity, &GlobalTransform), With<Player>>,
+    mut noises: EventWriter<Noise>,
+    mut timer: Local<FootstepTimer>,
+) {
+    if !actions.any_pressed([
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+    ]) {
+        // Start our stride over whenever we stop
+        timer.reset();
+        return;
+    }
+
+    timer.tick(time.delta());
+    if !timer.just_finished() {
+        return;
+    }
+
+    if let Ok((player, player_transform)) = player_qry.get_single() {
+        let loudness = if actions.pressed(Action::Sneak) {
+            SNEAKING_FOOTSTEP_LOUDNESS
+        } else {
+            FOOTSTEP_LOUDNESS
+        };
+
+        noises.send(Noise {
+            source: Some(player),
+            position: player_transform.translation().truncate(),
+            loudness,
+        });
+    }
+}
+
 pub fn player_face(
-    mut player_qry: Query<&mut Transform, With<Player>>,
+    actions: Res<ActionState>,
+    last_device: Res<LastInputDevice>,
+    mut player_qry: Query<(&Transform, &mut MoveIntent), With<Player>>,
     window_qry: Query<&Window, With<PrimaryWindow>>,
     camera_qry: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
 ) {
-    if let Ok(window) = window_qry.get_single() {
-        if let Some(cursor) = window.cursor_position() {
-            if let Ok((camera, camera_transform)) = camera_qry.get_single() {
-                if let Some(look) = camera.viewport_to_world_2d(camera_transform, cursor) {
-                    if let Ok(mut player_transform) = player_qry.get_single_mut() {
-                        let to = look - player_transform.translation.truncate();
-                        let facing = player_transform.local_x().truncate();
-                        let angle = facing.angle_between(to);
-                        if angle.abs() > f32::EPSILON {
-                            player_transform.rotate_z(facing.angle_between(to));
-                        }
-                    }
-                }
+    let Ok((player_transform, mut intent)) = player_qry.get_single_mut() else {
+        return;
+    };
+
+    let to = match *last_device {
+        LastInputDevice::KeyboardMouse => {
+            let Some(look) = cursor_world_position(&window_qry, &camera_qry) else {
+                return;
+            };
+            look - player_transform.translation.truncate()
+        }
+        LastInputDevice::Gamepad => {
+            let aim = Vec2::new(
+                actions.axis(Action::AimLeft, Action::AimRight),
+                actions.axis(Action::AimDown, Action::AimUp),
+            );
+            // Let go of the stick, and we keep facing wherever we were
+            if aim.length() < AIM_DEADZONE {
+                return;
             }
+            aim
         }
-    }
+    };
+
+    intent.facing = Some(to);
 }
@@ -0,0 +1,587 @@
+use std::{collections::BTreeMap, fs, io, path::Path, time::Duration};
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::{hostile_weapon, spawn_drone, DroneAI, DroneKind},
+    combat::{Projectile, ShotEffect, Weapon},
+    door::{Door, DoorState},
+    fov::ExploredMap,
+    hazard::HazardMap,
+    health::{Corpse, Died, Health},
+    input::{Action, ActionState},
+    inventory::{spawn_item, EquipSlot, Inventory, Item, ItemDefinitions, ItemId},
+    locomotion::MoveIntent,
+    map::{tile_at, Rooms, ShipParameters},
+    pathfinding::Waypoints,
+    player::Player,
+    ship_systems::ShipSystems,
+    sprites::Sprites,
+};
+
+/// Where the game is quick saved to, and quick loaded from
+pub const SAVE_PATH: &str = "saves/quicksave.ron";
+
+/// Version of the save file format; bump this whenever `SaveGame` changes incompatibly
+pub const SAVE_VERSION: u32 = 1;
+
+/// Just enough of a save file to tell which version it is, before trying to read the rest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+struct SaveVersion {
+    version: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedInventory {
+    pub items: Vec<ItemId>,
+    pub equipped: BTreeMap<EquipSlot, ItemId>,
+}
+
+/// The state of a weapon; what sort of weapon it is follows from whoever's holding it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedWeapon {
+    pub ammo: u32,
+    pub cooldown_elapsed: Duration,
+}
+
+impl SavedWeapon {
+    fn new(weapon: &Weapon) -> Self {
+        Self {
+            ammo: weapon.ammo,
+            cooldown_elapsed: weapon.cooldown_elapsed(),
+        }
+    }
+
+    fn restore(&self, weapon: &mut Weapon) {
+        weapon.ammo = self.ammo;
+        weapon.set_cooldown_elapsed(self.cooldown_elapsed);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedPlayer {
+    pub transform: Transform,
+    /// The player's health, or `None` if they're dead
+    ///
+    /// Only the current health is restored; maximum health follows from equipment.
+    pub health: Option<Health>,
+    pub inventory: Option<SavedInventory>,
+    pub weapon: Option<SavedWeapon>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedDrone {
+    pub kind: DroneKind,
+    pub transform: Transform,
+    pub health: Option<Health>,
+    /// Whether all that's left of the drone is its corpse
+    pub dead: bool,
+    pub weapon: Option<SavedWeapon>,
+    /// Which way the drone was turning to face, e.g. toward the player it's shooting at
+    pub facing: Option<Vec2>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SavedDoor {
+    /// The tile the door is centered on, which is how it's found again on loading
+    pub tile: IVec2,
+    pub state: DoorState,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedItem {
+    pub id: ItemId,
+    pub position: Vec2,
+}
+
+/// Everything needed to rebuild a game in progress
+///
+/// The ship itself isn't saved, only the parameters it was generated from, since the same
+/// parameters always generate the same ship.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    pub ship: ShipParameters,
+    pub player: Option<SavedPlayer>,
+    pub drones: Vec<SavedDrone>,
+    pub doors: Vec<SavedDoor>,
+    /// Items lying around in the world, rather than carried
+    pub items: Vec<SavedItem>,
+    pub explored: ExploredMap,
+    pub ship_systems: Option<ShipSystems>,
+    pub hazards: Option<HazardMap>,
+}
+
+impl SaveGame {
+    /// Load a saved game from a RON file, refusing saves from other versions of the game
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let invalid =
+            |err: ron::error::SpannedError| io::Error::new(io::ErrorKind::InvalidData, err);
+
+        let SaveVersion { version } = ron::from_str(&contents).map_err(invalid)?;
+        if version != SAVE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("save is version {version}, expected version {SAVE_VERSION}"),
+            ));
+        }
+        ron::from_str(&contents).map_err(invalid)
+    }
+
+    /// Save the game to a RON file, creating its directory if need be
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let contents = ron::ser::to_string_pretty(self, Default::default())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, contents)
+    }
+}
+
+/// A saved game waiting to be applied, as soon as the world it belongs in has been built
+#[derive(Debug, Clone, PartialEq, Resource)]
+pub struct PendingLoad(pub SaveGame);
+
+/// Check if the player wants to quick save
+pub fn quick_save_pressed(actions: Res<ActionState>) -> bool {
+    actions.just_pressed(Action::QuickSave)
+}
+
+/// Everything saved about the player
+type PlayerState = (
+    &'static Transform,
+    Option<&'static Health>,
+    Option<&'static Inventory>,
+    Option<&'static Weapon>,
+);
+
+/// Everything saved about a drone
+type DroneState = (
+    &'static DroneKind,
+    &'static Transform,
+    Option<&'static Health>,
+    Has<Corpse>,
+    Option<&'static Weapon>,
+    Option<&'static MoveIntent>,
+);
+
+/// Record the game in progress, for `write_quick_save` to save
+#[allow(clippy::too_many_arguments)]
+pub fn quick_save(
+    ship: Res<ShipParameters>,
+    explored: Res<ExploredMap>,
+    ship_systems: Option<Res<ShipSystems>>,
+    hazards: Option<Res<HazardMap>>,
+    player_qry: Query<PlayerState, With<Player>>,
+    drone_qry: Query<DroneState>,
+    door_qry: Query<(&Door, &Transform)>,
+    item_qry: Query<(&Item, &Transform)>,
+) -> SaveGame {
+    let player = player_qry
+        .get_single()
+        .ok()
+        .map(|(transform, health, inventory, weapon)| SavedPlayer {
+            transform: *transform,
+            health: health.copied(),
+            inventory: inventory.map(|inventory| SavedInventory {
+                items: inventory.items().to_vec(),
+                equipped: inventory
+                    .all_equipped()
+                    .map(|(slot, id)| (slot, id.clone()))
+                    .collect(),
+            }),
+            weapon: weapon.map(SavedWeapon::new),
+        });
+
+    SaveGame {
+        version: SAVE_VERSION,
+        ship: *ship,
+        player,
+        drones: drone_qry
+            .iter()
+            .map(
+                |(&kind, &transform, health, dead, weapon, intent)| SavedDrone {
+                    kind,
+                    transform,
+                    health: health.copied(),
+                    dead,
+                    weapon: weapon.map(SavedWeapon::new),
+                    facing: intent.and_then(|intent| intent.facing),
+                },
+            )
+            .collect(),
+        doors: door_qry
+            .iter()
+            .map(|(door, transform)| SavedDoor {
+                tile: tile_at(transform.translation.truncate()),
+                state: door.state,
+            })
+            .collect(),
+        items: item_qry
+            .iter()
+            .map(|(Item(id), transform)| SavedItem {
+                id: id.clone(),
+                position: transform.translation.truncate(),
+            })
+            .collect(),
+        explored: explored.clone(),
+        ship_systems: ship_systems.map(|systems| systems.clone()),
+        hazards: hazards.map(|hazards| hazards.clone()),
+    }
+}
+
+/// Save a game recorded by `quick_save` to `SAVE_PATH`
+pub fn write_quick_save(In(save): In<SaveGame>) {
+    match save.save(SAVE_PATH) {
+        Ok(()) => info!("Saved game to {SAVE_PATH}"),
+        Err(err) => warn!("Could not save game to {SAVE_PATH}: {err}"),
+    }
+}
+
+/// Load the game saved at `SAVE_PATH` when `Action::QuickLoad` is pressed
+pub fn quick_load(mut commands: Commands, actions: Res<ActionState>) {
+    if !actions.just_pressed(Action::QuickLoad) {
+        return;
+    }
+
+    match SaveGame::load(SAVE_PATH) {
+        Ok(save) => commands.insert_resource(PendingLoad(save)),
+        Err(err) => warn!("Could not load game from {SAVE_PATH}: {err}"),
+    }
+}
+
+/// Everything about the player that's restored on loading
+type LoadedPlayer = (
+    Entity,
+    &'static mut Transform,
+    Option<&'static mut Health>,
+    Option<&'static mut Inventory>,
+    Option<&'static mut Weapon>,
+    Option<&'static mut Waypoints>,
+);
+
+/// Everything that's despawned and respawned on loading, rather than restored in place
+type Respawned = Or<(
+    With<DroneAI>,
+    With<Item>,
+    With<Projectile>,
+    With<ShotEffect>,
+)>;
+
+/// Put the world into the state recorded by a pending saved game
+///
+/// Everything the ship's seed doesn't already decide, i.e. the player, drones, items, doors and
+/// the state of the ship's systems, is replaced with what was saved.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_pending_load(
+    mut commands: Commands,
+    sprites: Res<Sprites>,
+    item_defs: Res<ItemDefinitions>,
+    pending: Res<PendingLoad>,
+    rooms: Option<Res<Rooms>>,
+    mut ship: ResMut<ShipParameters>,
+    mut player_qry: Query<LoadedPlayer, With<Player>>,
+    mut door_qry: Query<(&Transform, &mut Door), Without<Player>>,
+    despawn_qry: Query<Entity, Respawned>,
+    mut deaths: EventWriter<Died>,
+) {
+    let PendingLoad(save) = &*pending;
+    commands.remove_resource::<PendingLoad>();
+
+    if rooms.is_some() && ship.seed != save.ship.seed {
+        warn!(
+            "Can't load a save of a different ship (seed {:?}) into this one (seed {:?})",
+            save.ship.seed, ship.seed
+        );
+        return;
+    }
+    *ship = save.ship;
+
+    if let (Ok((entity, mut transform, health, inventory, weapon, waypoints)), Some(saved)) =
+        (player_qry.get_single_mut(), &save.player)
+    {
+        *transform = saved.transform;
+        match (health, saved.health) {
+            (Some(mut health), Some(saved)) => health.current = saved.current,
+            (Some(_), None) => deaths.send(Died {
+                entity,
+                killer: None,
+                position: saved.transform.translation.truncate(),
+            }),
+            (None, Some(_)) => warn!("Can't bring the player back from the dead"),
+            (None, None) => {}
+        }
+        if let (Some(mut inventory), Some(saved)) = (inventory, &saved.inventory) {
+            inventory.replace(saved.items.clone(), saved.equipped.clone());
+        }
+        if let (Some(mut weapon), Some(saved)) = (weapon, &saved.weapon) {
+            saved.restore(&mut weapon);
+        }
+        if let Some(mut waypoints) = waypoints {
+            waypoints.clear();
+        }
+    }
+
+    // Drones and items come and go, so rather than match them up, replace them all
+    for entity in despawn_qry.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for drone in &save.drones {
+        let entity = spawn_drone(&mut commands, &sprites, drone.kind, drone.transform);
+        if let Some(health) = drone.health {
+            commands.entity(entity).insert(health);
+        }
+        // Only hostile drones are armed, or turn to face anything
+        if let Some(saved) = drone.weapon {
+            let mut weapon = hostile_weapon();
+            saved.restore(&mut weapon);
+            commands.entity(entity).insert(weapon);
+        }
+        if let Some(facing) = drone.facing {
+            commands.entity(entity).insert(MoveIntent {
+                facing: Some(facing),
+                ..Default::default()
+            });
+        }
+        if drone.dead {
+            deaths.send(Died {
+                entity,
+                killer: None,
+                position: drone.transform.translation.truncate(),
+            });
+        }
+    }
+    for item in &save.items {
+        spawn_item(&mut commands, &item_defs, item.id.clone(), item.position);
+    }
+
+    let doors = save
+        .doors
+        .iter()
+        .map(|door| (door.tile, door.state))
+        .collect::<HashMap<_, _>>();
+    for (transform, mut door) in door_qry.iter_mut() {
+        let tile = tile_at(transform.translation.truncate());
+        if let Some(&state) = doors.get(&tile).filter(|&&state| state != door.state) {
+            *door = Door::new(state);
+        }
+    }
+
+    commands.insert_resource(save.explored.clone());
+    if let Some(ship_systems) = &save.ship_systems {
+        commands.insert_resource(ship_systems.clone());
+    }
+    if let Some(hazards) = &save.hazards {
+        commands.insert_resource(hazards.clone());
+    }
+    info!("Loaded saved game");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        combat::FireMode,
+        health::{Damage, DamageKind},
+    };
+
+    /// The last game recorded by `quick_save`
+    #[derive(Resource)]
+    struct Snapshot(SaveGame);
+
+    fn keep_snapshot(In(save): In<SaveGame>, mut commands: Commands) {
+        commands.insert_resource(Snapshot(save));
+    }
+
+    fn snapshot(app: &App) -> SaveGame {
+        let mut save = app.world.resource::<Snapshot>().0.clone();
+        // Drones and items are respawned on loading, so needn't come back in the same order
+        save.drones.sort_by(|a, b| {
+            a.transform
+                .translation
+                .x
+                .total_cmp(&b.transform.translation.x)
+        });
+        save.items
+            .sort_by(|a, b| a.position.x.total_cmp(&b.position.x));
+        save
+    }
+
+    fn spawn_game(mut commands: Commands, sprites: Res<Sprites>, item_defs: Res<ItemDefinitions>) {
+        let mut health = Health::new(100.0);
+        health.current = 60.0;
+        let mut inventory = Inventory::new(50.0);
+        inventory.replace(
+            vec!["crate".into()],
+            [(EquipSlot::Head, ItemId::from("helmet"))],
+        );
+        let mut weapon = Weapon::new(
+            FireMode::Hitscan { range: 256.0 },
+            Damage::new(10.0, DamageKind::Energy),
+            0.5,
+        );
+        weapon.ammo = 5;
+        commands.spawn((
+            Player,
+            Transform::from_xyz(24.0, 40.0, 2.0),
+            health,
+            inventory,
+            weapon,
+        ));
+
+        let hostile = spawn_drone(
+            &mut commands,
+            &sprites,
+            DroneKind::Hostile,
+            Transform::from_xyz(-80.0, 8.0, 2.0),
+        );
+        // Part way through cooling down from a shot, while turning to take another
+        let mut weapon = hostile_weapon();
+        weapon.set_cooldown_elapsed(Duration::from_millis(400));
+        commands.entity(hostile).insert((
+            Health {
+                current: 25.0,
+                ..Health::new(40.0)
+            },
+            weapon,
+            MoveIntent {
+                facing: Some(Vec2::Y),
+                ..Default::default()
+            },
+        ));
+        spawn_drone(
+            &mut commands,
+            &sprites,
+            DroneKind::Companion,
+            Transform::from_xyz(40.0, 40.0, 2.0),
+        );
+
+        commands.spawn((
+            Door::new(DoorState::Open),
+            Transform::from_xyz(64.0, 32.0, 0.0),
+        ));
+        commands.spawn((
+            Door::new(DoorState::Locked),
+            Transform::from_xyz(128.0, 32.0, 0.0),
+        ));
+
+        spawn_item(
+            &mut commands,
+            &item_defs,
+            "visor".into(),
+            Vec2::new(8.0, 8.0),
+        );
+        spawn_item(
+            &mut commands,
+            &item_defs,
+            "boots".into(),
+            Vec2::new(96.0, 24.0),
+        );
+
+        let bounds = IRect::new(-8, -4, 12, 4);
+        let mut explored = ExploredMap::new(bounds);
+        explored.reveal(IVec2::new(1, 2));
+        let mut hazards = HazardMap::new(bounds);
+        hazards.ignite(IVec2::new(3, 1), 0.5);
+        let mut ship_systems = ShipSystems::default();
+        ship_systems.engine_room = Some(1);
+        ship_systems.engine_running = true;
+        commands.insert_resource(explored);
+        commands.insert_resource(hazards);
+        commands.insert_resource(ship_systems);
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<Died>()
+            .init_resource::<Sprites>()
+            .init_resource::<ItemDefinitions>()
+            .insert_resource(ShipParameters {
+                seed: Some(7),
+                ..Default::default()
+            })
+            .add_systems(Startup, spawn_game)
+            .add_systems(
+                Update,
+                (
+                    quick_save.pipe(keep_snapshot),
+                    apply_pending_load.run_if(resource_exists::<PendingLoad>()),
+                ),
+            );
+        app
+    }
+
+    #[test]
+    fn saves_survive_being_written_and_read() {
+        let mut app = app();
+        app.update();
+        let save = snapshot(&app);
+        assert!(save.player.is_some());
+        assert_eq!(save.drones.len(), 2);
+
+        let contents = ron::ser::to_string_pretty(&save, Default::default()).unwrap();
+        let loaded: SaveGame = ron::from_str(&contents).unwrap();
+
+        assert_eq!(loaded, save);
+    }
+
+    #[test]
+    fn loading_restores_a_quick_save() {
+        let mut app = app();
+        app.update();
+        let save = snapshot(&app);
+
+        // Play on for a bit, changing everything that's saved
+        let player = app
+            .world
+            .query_filtered::<Entity, With<Player>>()
+            .single(&app.world);
+        let mut player = app.world.entity_mut(player);
+        player.get_mut::<Transform>().unwrap().translation = Vec3::new(-100.0, 0.0, 2.0);
+        player.get_mut::<Health>().unwrap().current = 10.0;
+        player
+            .get_mut::<Inventory>()
+            .unwrap()
+            .replace(Vec::new(), []);
+        player.get_mut::<Weapon>().unwrap().ammo = 0;
+
+        let drones = app
+            .world
+            .query_filtered::<Entity, With<DroneAI>>()
+            .iter(&app.world)
+            .collect::<Vec<_>>();
+        app.world.despawn(drones[0]);
+        let items = app
+            .world
+            .query_filtered::<Entity, With<Item>>()
+            .iter(&app.world)
+            .collect::<Vec<_>>();
+        app.world.despawn(items[1]);
+        for mut door in app.world.query::<&mut Door>().iter_mut(&mut app.world) {
+            *door = Door::new(DoorState::Broken);
+        }
+
+        app.world
+            .resource_mut::<ExploredMap>()
+            .reveal(IVec2::new(-5, -2));
+        app.world.resource_mut::<ShipSystems>().engine_running = false;
+        app.world
+            .resource_mut::<HazardMap>()
+            .ignite(IVec2::new(-6, 0), 1.0);
+        app.update();
+        assert_ne!(snapshot(&app), save);
+
+        app.insert_resource(PendingLoad(save.clone()));
+        app.update();
+        // Everything the load spawned or inserted exists by the next frame
+        app.update();
+
+        assert!(!app.world.contains_resource::<PendingLoad>());
+        assert_eq!(snapshot(&app), save);
+    }
+}
@@ -4,24 +4,35 @@ use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
 use crate::{
-    ai::DroneAI,
-    core::{OPAQUE_GROUP, PLAYER_GROUP},
-    fov::{FieldOfView, Viewable},
+    ai::{spawn_drone, DroneKind},
+    core::{OPAQUE_GROUP, TRANSLUCENT_GROUP},
+    door::{spawn_door, DoorState},
+    fov::{Occluder, Viewable},
+    interaction::{Interactable, InteractionKind},
+    inventory::{spawn_item, ItemDefinitions},
+    lighting::LightSource,
+    sprites::Sprites,
 };
 
-pub(crate) fn setup_test_entities(mut commands: Commands, asset_server: Res<AssetServer>) {
-    // Spawn a drone that will share FoV with the player
+pub(crate) fn setup_test_entities(
+    mut commands: Commands,
+    sprites: Res<Sprites>,
+    item_defs: Res<ItemDefinitions>,
+) {
+    // Spawn a drone that will share FoV with the player, and a hostile one
     let drone_transform = Transform::from_xyz(179.0, 128.0, 5.0);
-    commands.spawn((
-        SpriteBundle {
-            texture: asset_server.load("drone.png"),
-            transform: drone_transform.with_rotation(Quat::from_rotation_z(TAU / 1.8)),
-            ..Default::default()
-        },
-        CollisionGroups::new(PLAYER_GROUP, Group::all()),
-        FieldOfView::new(128.0, TAU / 10.0),
-        DroneAI,
-    ));
+    spawn_drone(
+        &mut commands,
+        &sprites,
+        DroneKind::Companion,
+        drone_transform.with_rotation(Quat::from_rotation_z(TAU / 1.8)),
+    );
+    spawn_drone(
+        &mut commands,
+        &sprites,
+        DroneKind::Hostile,
+        Transform::from_xyz(-160.0, -96.0, 5.0),
+    );
 
     // Spawn a collider so we can see how/if physics works
     commands.spawn((
@@ -38,13 +49,78 @@ pub(crate) fn setup_test_entities(mut commands: Commands, asset_server: Res<Asse
         CollisionGroups::new(OPAQUE_GROUP, Group::all()),
     ));
 
+    // Light up the area around the player's starting position
+    commands.spawn((
+        TransformBundle::from_transform(Transform::from_xyz(0.0, 32.0, 0.0)),
+        LightSource::point(160.0, 1.0),
+    ));
+
+    // Spawn a window and some smoke so we can test seeing through things
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform::from_xyz(48.0, 128.0, 1.0),
+            sprite: Sprite {
+                color: Color::CYAN.with_a(0.3),
+                custom_size: Some(Vec2::new(32.0, 32.0)),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Collider::cuboid(16.0, 16.0),
+        CollisionGroups::new(TRANSLUCENT_GROUP, Group::all()),
+        Occluder::WINDOW,
+    ));
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform::from_xyz(-64.0, 64.0, 1.0),
+            sprite: Sprite {
+                color: Color::GRAY.with_a(0.5),
+                custom_size: Some(Vec2::splat(48.0)),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Collider::ball(24.0),
+        Sensor,
+        CollisionGroups::new(TRANSLUCENT_GROUP, Group::all()),
+        Occluder::new(0.4),
+    ));
+
+    // Spawn a door beside the collider, so we can test opening and closing it
+    spawn_door(
+        &mut commands,
+        Vec2::new(-48.0, 128.0),
+        Vec2::X,
+        DoorState::Closed,
+    );
+
+    // Spawn a terminal so we can test interacting with things
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform::from_xyz(-96.0, 32.0, 1.0),
+            sprite: Sprite {
+                color: Color::DARK_GREEN,
+                custom_size: Some(Vec2::splat(16.0)),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Interactable::new(InteractionKind::Use),
+        Viewable::Static,
+    ));
+
+    // Spawn some items so we can test picking them up and equipping them
+    for (id, x, y) in [("sensor_array", -48.0, 0.0), ("scrap_metal", -64.0, -32.0)] {
+        spawn_item(&mut commands, &item_defs, id.into(), Vec2::new(x, y));
+    }
+
     // Spawn a few sprites so we can test field of view
     for (x, y) in [(128.0, 96.0), (96.0, 128.0), (-128.0, -32.0), (32.0, 0.0)] {
         let transform = Transform::from_xyz(x, y, 0.0);
         commands.spawn((
             SpriteBundle {
                 transform,
-                texture: asset_server.load("bevy_icon_32.png"),
+                texture: sprites.icon.clone(),
                 ..Default::default()
             },
             Viewable::Dynamic,
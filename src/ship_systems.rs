@@ -0,0 +1,531 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    door::DoorRooms,
+    fov::Viewable,
+    health::{Damage, DamageKind, TakeDamage},
+    lighting::RoomLight,
+    map::{tile_at, tile_center, Rooms, ShipParameters, TILE_SIZE},
+    rand::*,
+};
+
+/// How quickly air flows between rooms through an open door, in tiles of air per second for each
+/// atmosphere of pressure difference
+const DOOR_FLOW_RATE: f32 = 40.0;
+
+/// How quickly air vents out of a hull breach of size `1.0`, in tiles of air per second at one
+/// atmosphere of pressure
+const BREACH_FLOW_RATE: f32 = 30.0;
+
+/// How quickly life support restores pressure and oxygen in a powered room, per second
+const LIFE_SUPPORT_RATE: f32 = 0.02;
+
+/// How quickly oxygen runs out in a room without power, per second
+const OXYGEN_DECAY_RATE: f32 = 0.002;
+
+/// How likely each room is to start out with a breach in its hull
+const ROOM_BREACH_CHANCE: f64 = 0.1;
+
+/// How big hull breaches can be
+const MIN_BREACH_SIZE: f32 = 0.05;
+const MAX_BREACH_SIZE: f32 = 0.2;
+
+const BREACH_Z: f32 = 3.0;
+
+/// How many rooms the engine can power
+const ENGINE_OUTPUT: usize = 12;
+
+/// How hard escaping air pulls things toward where it's escaping, in pixels per second for each
+/// tile of air per second
+const DECOMPRESSION_FORCE: f32 = 4.0;
+
+/// Below these, it's impossible to breathe
+const MIN_BREATHABLE_PRESSURE: f32 = 0.5;
+const MIN_BREATHABLE_OXYGEN: f32 = 0.4;
+
+/// How much damage not being able to breathe does, per second
+const SUFFOCATION_DAMAGE: f32 = 5.0;
+
+/// The state of a single room's systems
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RoomSystems {
+    /// Air pressure, in atmospheres
+    pub pressure: f32,
+    /// How breathable the air is, from `0.0` (not at all) to `1.0` (freshly scrubbed)
+    pub oxygen: f32,
+    pub powered: bool,
+}
+
+impl Default for RoomSystems {
+    fn default() -> Self {
+        Self {
+            pressure: 1.0,
+            oxygen: 1.0,
+            powered: true,
+        }
+    }
+}
+
+impl RoomSystems {
+    pub fn is_breathable(&self) -> bool {
+        self.pressure >= MIN_BREATHABLE_PRESSURE && self.oxygen >= MIN_BREATHABLE_OXYGEN
+    }
+}
+
+/// Somewhere air is leaving a room, and how quickly, for decompression to pull things toward
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Vent {
+    room: usize,
+    position: Vec2,
+    /// In tiles of air per second
+    rate: f32,
+}
+
+/// The state of every room's power, life support and atmosphere, indexed the same as `Rooms`
+#[derive(Debug, Default, Clone, PartialEq, Resource, Serialize, Deserialize)]
+pub struct ShipSystems {
+    rooms: Vec<RoomSystems>,
+    /// The room the engine is in, which power is distributed from
+    pub engine_room: Option<usize>,
+    /// Whether the engine is running at all
+    pub engine_running: bool,
+    /// Recalculated every frame, so never worth saving
+    #[serde(skip)]
+    vents: Vec<Vent>,
+}
+
+impl ShipSystems {
+    pub fn room(&self, idx: usize) -> Option<&RoomSystems> {
+        self.rooms.get(idx)
+    }
+
+    pub fn room_mut(&mut self, idx: usize) -> Option<&mut RoomSystems> {
+        self.rooms.get_mut(idx)
+    }
+
+    /// Move air between rooms through open doors, out of the ship through hull breaches, and keep
+    /// it breathable with life support, over `dt` seconds
+    ///
+    /// `open_doors` are the rooms each open door joins and where it is, and `breaches` the size of
+    /// each breach and where it is. This depends on nothing else, so the same ship with the same
+    /// doors open always breathes the same way.
+    pub fn step_atmosphere(
+        &mut self,
+        rooms: &Rooms,
+        open_doors: &[(DoorRooms, Vec2)],
+        breaches: &[(HullBreach, Vec2)],
+        dt: f32,
+    ) {
+        if dt <= 0.0 {
+            return;
+        }
+
+        // Work in amounts of air, rather than pressure, so that big rooms fill small ones
+        let volumes = (0..self.rooms.len())
+            .map(|idx| room_volume(rooms, idx))
+            .collect::<Vec<_>>();
+        let mut air = self
+            .rooms
+            .iter()
+            .zip(&volumes)
+            .map(|(room, volume)| room.pressure * volume)
+            .collect::<Vec<_>>();
+        let mut oxygen = self
+            .rooms
+            .iter()
+            .zip(&air)
+            .map(|(room, air)| room.oxygen * air)
+            .collect::<Vec<_>>();
+        let mut vents = Vec::new();
+
+        for &(DoorRooms(a, b), position) in open_doors {
+            if a >= air.len() || b >= air.len() {
+                continue;
+            }
+            let (from, to) = if air[a] / volumes[a] >= air[b] / volumes[b] {
+                (a, b)
+            } else {
+                (b, a)
+            };
+            let difference = air[from] / volumes[from] - air[to] / volumes[to];
+            // Never move more air than it takes to even the two rooms out
+            let equalize = difference * volumes[from] * volumes[to] / (volumes[from] + volumes[to]);
+            let moved = (difference * DOOR_FLOW_RATE * dt).min(equalize);
+            if moved <= 0.0 {
+                continue;
+            }
+
+            let moved_oxygen = oxygen[from] * moved / air[from];
+            air[from] -= moved;
+            air[to] += moved;
+            oxygen[from] -= moved_oxygen;
+            oxygen[to] += moved_oxygen;
+            vents.push(Vent {
+                room: from,
+                position,
+                rate: moved / dt,
+            });
+        }
+
+        for &(breach, position) in breaches {
+            let Some(idx) = rooms
+                .room_at(tile_at(position))
+                .filter(|&idx| idx < air.len())
+            else {
+                continue;
+            };
+            let pressure = air[idx] / volumes[idx];
+            let vented = (pressure * breach.size * BREACH_FLOW_RATE * dt).min(air[idx]);
+            if vented <= 0.0 {
+                continue;
+            }
+
+            oxygen[idx] -= oxygen[idx] * vented / air[idx];
+            air[idx] -= vented;
+            vents.push(Vent {
+                room: idx,
+                position,
+                rate: vented / dt,
+            });
+        }
+
+        for (idx, room) in self.rooms.iter_mut().enumerate() {
+            room.pressure = air[idx] / volumes[idx];
+            room.oxygen = if air[idx] > 0.0 {
+                (oxygen[idx] / air[idx]).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+
+            if room.powered {
+                room.pressure = (room.pressure + LIFE_SUPPORT_RATE * dt).min(1.0);
+                room.oxygen = (room.oxygen + LIFE_SUPPORT_RATE * dt).min(1.0);
+            } else {
+                room.oxygen = (room.oxygen - OXYGEN_DECAY_RATE * dt).max(0.0);
+            }
+        }
+        self.vents = vents;
+    }
+}
+
+/// A hole in the hull, venting a room's air into space
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct HullBreach {
+    /// How big the hole is; air escapes in proportion to this
+    pub size: f32,
+}
+
+/// Tag component for anything that needs air to breathe
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
+pub struct Breathes;
+
+/// How much air a room holds at one atmosphere, in tiles
+pub fn room_volume(rooms: &Rooms, idx: usize) -> f32 {
+    rooms
+        .get(idx)
+        .map(|room| (room.width() * room.height()) as f32)
+        .unwrap_or(1.0)
+        .max(1.0)
+}
+
+/// Set up every room's systems, with the engine in the room furthest aft, light every room, and
+/// breach the hull of a few
+pub fn setup_ship_systems(mut commands: Commands, rooms: Res<Rooms>, ship: Res<ShipParameters>) {
+    let engine_room = rooms
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, room)| room.center().x)
+        .map(|(idx, _)| idx);
+
+    commands.insert_resource(ShipSystems {
+        rooms: vec![RoomSystems::default(); rooms.len()],
+        engine_room,
+        engine_running: true,
+        vents: Vec::new(),
+    });
+
+    for &room in rooms.iter() {
+        commands.spawn(RoomLight {
+            bounds: room,
+            intensity: 0.6,
+            powered: true,
+        });
+    }
+
+    let mut rng = seed_rng((ship.seed, "breaches"));
+    for room in rooms.iter() {
+        // Empty rooms can't be breached, but still take their turn so later rooms keep theirs
+        let breached = rng.gen_bool(ROOM_BREACH_CHANCE);
+        let size = rng.gen_range(MIN_BREACH_SIZE..MAX_BREACH_SIZE);
+        let along = rng.gen_range(0.0..1.0);
+        let side = rng.gen_range(0..4);
+        if !breached || room.is_empty() {
+            continue;
+        }
+
+        // Breaches are in the hull, so somewhere along the room's edge
+        let (min, max) = (room.min.as_vec2(), (room.max - IVec2::ONE).as_vec2());
+        let point = min + (max - min) * along;
+        let tile = match side {
+            0 => Vec2::new(point.x, min.y),
+            1 => Vec2::new(point.x, max.y),
+            2 => Vec2::new(min.x, point.y),
+            _ => Vec2::new(max.x, point.y),
+        }
+        .round()
+        .as_ivec2();
+        spawn_breach(&mut commands, tile_center(tile), size);
+    }
+}
+
+/// Spawn a breach in the hull, centered on `position`
+pub fn spawn_breach(commands: &mut Commands, position: Vec2, size: f32) -> Entity {
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::BLACK,
+                    custom_size: Some(Vec2::splat(TILE_SIZE * (0.25 + size))),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(position.extend(BREACH_Z)),
+                ..Default::default()
+            },
+            HullBreach { size },
+            Viewable::Static,
+        ))
+        .id()
+}
+
+/// Power rooms outward from the engine room, for as many rooms as the engine can power
+pub fn distribute_power(
+    rooms: Res<Rooms>,
+    mut ship: ResMut<ShipSystems>,
+    mut light_qry: Query<&mut RoomLight>,
+) {
+    let mut powered = vec![false; ship.rooms.len()];
+    if let Some(engine_room) = ship.engine_room.filter(|_| ship.engine_running) {
+        let mut queue = VecDeque::from([engine_room]);
+        let mut supplied = 0;
+        while let Some(idx) = queue.pop_front() {
+            if powered[idx] || supplied >= ENGINE_OUTPUT {
+                continue;
+            }
+            powered[idx] = true;
+            supplied += 1;
+            queue.extend(rooms.connected(idx).filter(|&next| !powered[next]));
+        }
+    }
+
+    for (room, &powered) in ship
+        .bypass_change_detection()
+        .rooms
+        .iter_mut()
+        .zip(&powered)
+    {
+        room.powered = powered;
+    }
+
+    for mut light in light_qry.iter_mut() {
+        let Some(idx) = rooms.iter().position(|&room| room == light.bounds) else {
+            continue;
+        };
+        let powered = powered.get(idx).copied().unwrap_or(false);
+        if light.powered != powered {
+            light.powered = powered;
+        }
+    }
+}
+
+/// Pull anything that moves itself toward wherever its room's air is escaping
+pub fn apply_decompression(
+    time: Res<Time>,
+    rooms: Res<Rooms>,
+    ship: Res<ShipSystems>,
+    mut mover_qry: Query<(&GlobalTransform, &mut KinematicCharacterController)>,
+) {
+    if ship.vents.is_empty() {
+        return;
+    }
+
+    for (transform, mut controller) in mover_qry.iter_mut() {
+        let position = transform.translation().truncate();
+        let Some(idx) = rooms.room_at(tile_at(position)) else {
+            continue;
+        };
+
+        let pull = ship
+            .vents
+            .iter()
+            .filter(|vent| vent.room == idx)
+            .map(|vent| {
+                let offset = vent.position - position;
+                // Air rushes hardest right by the vent
+                let falloff = (TILE_SIZE / offset.length().max(TILE_SIZE)).sqrt();
+                offset.normalize_or_zero() * vent.rate * falloff
+            })
+            .sum::<Vec2>();
+        if pull == Vec2::ZERO {
+            continue;
+        }
+
+        // Push the controller along on top of however it's already moving this frame
+        *controller.translation.get_or_insert(Vec2::ZERO) +=
+            pull * DECOMPRESSION_FORCE * time.delta_seconds();
+    }
+}
+
+/// Hurt anything that needs to breathe, but is somewhere without breathable air
+pub fn suffocate(
+    time: Res<Time>,
+    rooms: Res<Rooms>,
+    ship: Res<ShipSystems>,
+    breather_qry: Query<(Entity, &GlobalTransform), With<Breathes>>,
+    mut damages: EventWriter<TakeDamage>,
+) {
+    let amount = SUFFOCATION_DAMAGE * time.delta_seconds();
+    if amount <= 0.0 {
+        return;
+    }
+
+    for (breather, transform) in breather_qry.iter() {
+        let tile = tile_at(transform.translation().truncate());
+        let breathable = rooms
+            .room_at(tile)
+            .and_then(|idx| ship.room(idx))
+            // Outside the ship is nowhere to breathe
+            .map(RoomSystems::is_breathable)
+            .unwrap_or(false);
+        if !breathable {
+            damages.send(TakeDamage {
+                target: breather,
+                source: None,
+                damage: Damage::new(amount, DamageKind::Vacuum),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two 4×4 rooms side by side, the first full of air and the second empty, with the door
+    /// between them at `DOOR` and nothing powered
+    fn setup() -> (Rooms, ShipSystems) {
+        let rooms = Rooms::new(vec![IRect::new(0, 0, 4, 4), IRect::new(4, 0, 8, 4)]);
+        let unpowered = RoomSystems {
+            powered: false,
+            ..Default::default()
+        };
+        let ship = ShipSystems {
+            rooms: vec![
+                unpowered,
+                RoomSystems {
+                    pressure: 0.0,
+                    oxygen: 0.0,
+                    ..unpowered
+                },
+            ],
+            ..Default::default()
+        };
+
+        (rooms, ship)
+    }
+
+    const DOOR: (DoorRooms, Vec2) = (DoorRooms(0, 1), Vec2::new(4.0 * TILE_SIZE, TILE_SIZE * 2.0));
+
+    fn total_air(rooms: &Rooms, ship: &ShipSystems) -> f32 {
+        ship.rooms
+            .iter()
+            .enumerate()
+            .map(|(idx, room)| room.pressure * room_volume(rooms, idx))
+            .sum()
+    }
+
+    #[test]
+    fn closed_doors_keep_air_in() {
+        let (rooms, mut ship) = setup();
+        let before = ship.clone();
+
+        ship.step_atmosphere(&rooms, &[], &[], 1.0);
+
+        assert_eq!(
+            ship.room(0).unwrap().pressure,
+            before.room(0).unwrap().pressure
+        );
+        assert_eq!(
+            ship.room(1).unwrap().pressure,
+            before.room(1).unwrap().pressure
+        );
+        assert!(ship.vents.is_empty());
+    }
+
+    #[test]
+    fn open_doors_even_rooms_out() {
+        let (rooms, mut ship) = setup();
+        let air = total_air(&rooms, &ship);
+
+        ship.step_atmosphere(&rooms, &[DOOR], &[], 0.001);
+        let (first, second) = (*ship.room(0).unwrap(), *ship.room(1).unwrap());
+        assert!(first.pressure < 1.0 && second.pressure > 0.0);
+        assert!(first.pressure > second.pressure);
+        assert_eq!(ship.vents.len(), 1);
+        assert_eq!(ship.vents[0].room, 0);
+
+        // However long it's left, air never flows past evening out, nor appears from nowhere
+        ship.step_atmosphere(&rooms, &[DOOR], &[], 100.0);
+        let (first, second) = (*ship.room(0).unwrap(), *ship.room(1).unwrap());
+        assert!((first.pressure - 0.5).abs() < 1e-4);
+        assert!((second.pressure - 0.5).abs() < 1e-4);
+        assert!((total_air(&rooms, &ship) - air).abs() < 1e-3);
+        // The air that flowed brought its oxygen with it
+        assert!((first.oxygen - second.oxygen).abs() < 1e-4);
+    }
+
+    #[test]
+    fn breaches_vent_air() {
+        let (rooms, mut ship) = setup();
+        let breach = (HullBreach { size: 0.1 }, tile_center(IVec2::new(0, 2)));
+
+        ship.step_atmosphere(&rooms, &[], &[breach], 0.1);
+        let pressure = ship.room(0).unwrap().pressure;
+        assert!(pressure < 1.0);
+        assert_eq!(ship.vents.len(), 1);
+        assert_eq!(ship.vents[0].room, 0);
+
+        // Eventually, every last bit of air is gone
+        for _ in 0..1000 {
+            ship.step_atmosphere(&rooms, &[], &[breach], 1.0);
+        }
+        assert!(ship.room(0).unwrap().pressure < 0.01);
+        assert!(!ship.room(0).unwrap().is_breathable());
+
+        // Breaches outside the ship don't vent anything
+        let (rooms, mut ship) = setup();
+        let outside = (HullBreach { size: 1.0 }, tile_center(IVec2::new(-5, 0)));
+        ship.step_atmosphere(&rooms, &[], &[outside], 1.0);
+        assert_eq!(ship.room(0).unwrap().pressure, 1.0);
+    }
+
+    #[test]
+    fn life_support_needs_power() {
+        let (rooms, mut ship) = setup();
+        ship.room_mut(1).unwrap().powered = true;
+
+        ship.step_atmosphere(&rooms, &[], &[], 10.0);
+        assert!(ship.room(1).unwrap().pressure > 0.0);
+        assert!(ship.room(1).unwrap().oxygen > 0.0);
+        // Without power, the air slowly goes stale
+        assert!(ship.room(0).unwrap().oxygen < 1.0);
+
+        for _ in 0..100 {
+            ship.step_atmosphere(&rooms, &[], &[], 10.0);
+        }
+        assert_eq!(*ship.room(1).unwrap(), RoomSystems::default());
+    }
+}
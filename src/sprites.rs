@@ -1,13 +1,35 @@
-use bevy::prelude::*;
+use bevy::{asset::LoadState, prelude::*};
 
 #[derive(Debug, Default, Clone, Resource)]
 pub struct Sprites {
     pub player: Handle<Image>,
+    pub drone: Handle<Image>,
+    pub icon: Handle<Image>,
+    pub nebula: Handle<Image>,
+}
+
+impl Sprites {
+    pub fn iter(&self) -> impl Iterator<Item = &Handle<Image>> {
+        [&self.player, &self.drone, &self.icon, &self.nebula].into_iter()
+    }
+
+    /// Check if every sprite is done loading, whether or not it loaded successfully
+    pub fn is_loaded(&self, asset_server: &AssetServer) -> bool {
+        self.iter().all(|handle| {
+            matches!(
+                asset_server.get_load_state(handle),
+                Some(LoadState::Loaded | LoadState::Failed)
+            )
+        })
+    }
 }
 
 pub fn load_sprites(mut commands: Commands, asset_server: Res<AssetServer>) {
     let sprites = Sprites {
         player: asset_server.load("robot.png"),
+        drone: asset_server.load("drone.png"),
+        icon: asset_server.load("bevy_icon_32.png"),
+        nebula: asset_server.load("nebula.png"),
     };
 
     commands.insert_resource(sprites);
@@ -0,0 +1,127 @@
+use bevy::{prelude::*, time::Real};
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    input::{Action, ActionState},
+    locomotion::MoveIntent,
+    pathfinding::Waypoints,
+    player::Player,
+};
+
+/// How long the world runs for after the player does something in turn-based mode, in real seconds
+const TURN_TIME: f32 = 0.25;
+
+/// How quickly gameplay time passes
+///
+/// Everything driven by Bevy's default (virtual) `Time`, which includes gameplay systems and
+/// physics, is scaled by this; anything that must keep running at normal speed, e.g. UI, should use
+/// `Time<Real>` instead.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimeMode {
+    /// Time passes normally
+    #[default]
+    RealTime,
+    /// Time passes slowly, giving the player a chance to think
+    Tactical,
+    /// Time only passes while the player is doing something
+    TurnBased,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Resource)]
+pub struct TimeControl {
+    pub mode: TimeMode,
+    /// How fast time passes in `TimeMode::Tactical`, relative to real time
+    pub tactical_speed: f32,
+    /// How much longer, in real seconds, the world runs for the player's last action in
+    /// `TimeMode::TurnBased`
+    turn_remaining: f32,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        Self {
+            mode: TimeMode::default(),
+            tactical_speed: 0.25,
+            turn_remaining: 0.0,
+        }
+    }
+}
+
+impl TimeControl {
+    /// Switch to `mode`, or back to real time if we're already in it
+    pub fn toggle(&mut self, mode: TimeMode) {
+        self.mode = if self.mode == mode {
+            TimeMode::RealTime
+        } else {
+            mode
+        };
+        self.turn_remaining = 0.0;
+    }
+}
+
+pub fn player_time_control(actions: Res<ActionState>, mut control: ResMut<TimeControl>) {
+    if actions.just_pressed(Action::ToggleTactical) {
+        control.toggle(TimeMode::Tactical);
+        info!("Time mode: {:?}", control.mode);
+    }
+    if actions.just_pressed(Action::ToggleTurnBased) {
+        control.toggle(TimeMode::TurnBased);
+        info!("Time mode: {:?}", control.mode);
+    }
+}
+
+/// Speed up, slow down or pause gameplay time according to `TimeControl`
+pub fn update_time_control(
+    real_time: Res<Time<Real>>,
+    actions: Res<ActionState>,
+    player_qry: Query<(&MoveIntent, Option<&Waypoints>), With<Player>>,
+    mut control: ResMut<TimeControl>,
+    mut time: ResMut<Time<Virtual>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    let speed = match control.mode {
+        TimeMode::RealTime => 1.0,
+        TimeMode::Tactical => control.tactical_speed,
+        TimeMode::TurnBased => {
+            // Walking counts as acting for as long as we keep walking; anything else is one turn
+            let walking = player_qry
+                .get_single()
+                .map(|(intent, waypoints)| {
+                    intent.direction != Vec2::ZERO
+                        || waypoints
+                            .map(|waypoints| !waypoints.is_empty())
+                            .unwrap_or(false)
+                })
+                .unwrap_or(false);
+            if [Action::Interact, Action::Fire, Action::Reload]
+                .into_iter()
+                .any(|action| actions.just_pressed(action))
+            {
+                control.turn_remaining = TURN_TIME;
+            }
+
+            let acting = walking || control.turn_remaining > 0.0;
+            control.turn_remaining = (control.turn_remaining - real_time.delta_seconds()).max(0.0);
+            if acting {
+                1.0
+            } else {
+                0.0
+            }
+        }
+    };
+
+    if speed > 0.0 {
+        if time.is_paused() {
+            time.unpause();
+        }
+        if time.relative_speed() != speed {
+            time.set_relative_speed(speed);
+        }
+    } else if !time.is_paused() {
+        time.pause();
+    }
+    // Physics follows gameplay time, but don't even step it while paused
+    if rapier_config.physics_pipeline_active == time.is_paused() {
+        rapier_config.physics_pipeline_active = !time.is_paused();
+    }
+}
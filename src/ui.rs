@@ -3,6 +3,15 @@ use bevy::{
     prelude::*,
 };
 
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    core::Persistent,
+    input::{Action, ActionState},
+    inventory::{drop_item, EquipSlot, Inventory, ItemDefinitions},
+    player::Player,
+};
+
 /// Tag component for FPS counter
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
 pub struct FpsCounter;
@@ -24,6 +33,7 @@ pub fn setup_fps_counter(mut commands: Commands) {
     commands
         .spawn((
             FpsCounter,
+            Persistent,
             NodeBundle {
                 background_color: Color::NAVY.with_a(0.5).into(),
                 z_index: ZIndex::Global(i32::MAX),
@@ -72,7 +82,7 @@ pub fn update_fps(
     diagnostics: Res<DiagnosticsStore>,
     mut text_qry: Query<&mut Text, With<FpsText>>,
     mut timer: Local<FpsUpdateTimer>,
-    time: Res<Time>,
+    time: Res<Time<Real>>,
 ) {
     timer.tick(time.delta());
 
@@ -92,9 +102,9 @@ pub fn update_fps(
 
 pub fn toggle_fps_counter(
     mut counter_qry: Query<&mut Visibility, With<FpsCounter>>,
-    keyboard: Res<Input<KeyCode>>,
+    actions: Res<ActionState>,
 ) {
-    if keyboard.just_pressed(KeyCode::F12) {
+    if actions.just_pressed(Action::ToggleFps) {
         for mut vis in counter_qry.iter_mut() {
             *vis = match *vis {
                 Visibility::Hidden => Visibility::Visible,
@@ -103,3 +113,96 @@ pub fn toggle_fps_counter(
         }
     }
 }
+
+/// Something the player asked to do with their inventory
+enum InventoryCommand {
+    Equip(usize),
+    Unequip(EquipSlot),
+    Drop(usize),
+}
+
+pub fn inventory_panel(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    actions: Res<ActionState>,
+    defs: Res<ItemDefinitions>,
+    mut player_qry: Query<(&mut Inventory, &GlobalTransform), With<Player>>,
+    mut open: Local<bool>,
+) {
+    if actions.just_pressed(Action::ToggleInventory) {
+        *open = !*open;
+    }
+    if !*open {
+        return;
+    }
+    let Ok((mut inventory, player_transform)) = player_qry.get_single_mut() else {
+        return;
+    };
+
+    let mut command = None;
+    egui::Window::new("Inventory")
+        .anchor(egui::Align2::LEFT_TOP, egui::Vec2::new(5.0, 5.0))
+        .collapsible(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!(
+                "Weight: {:.1} / {:.1}",
+                inventory.weight(&defs),
+                inventory.max_weight
+            ));
+
+            ui.separator();
+            ui.heading("Equipped");
+            for slot in [
+                EquipSlot::Head,
+                EquipSlot::Body,
+                EquipSlot::Legs,
+                EquipSlot::Sensor,
+            ] {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{slot:?}:"));
+                    match inventory.equipped(slot) {
+                        Some(id) => {
+                            ui.label(defs.name(id));
+                            if ui.small_button("Unequip").clicked() {
+                                command = Some(InventoryCommand::Unequip(slot));
+                            }
+                        }
+                        None => {
+                            ui.weak("Nothing");
+                        }
+                    }
+                });
+            }
+
+            ui.separator();
+            ui.heading("Carried");
+            for (index, id) in inventory.items().iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(defs.name(id));
+                    let equippable = defs.get(id).and_then(|def| def.slot).is_some();
+                    if equippable && ui.small_button("Equip").clicked() {
+                        command = Some(InventoryCommand::Equip(index));
+                    }
+                    if ui.small_button("Drop").clicked() {
+                        command = Some(InventoryCommand::Drop(index));
+                    }
+                });
+            }
+        });
+
+    match command {
+        Some(InventoryCommand::Equip(index)) => {
+            inventory.equip(index, &defs);
+        }
+        Some(InventoryCommand::Unequip(slot)) => inventory.unequip(slot),
+        Some(InventoryCommand::Drop(index)) => drop_item(
+            &mut commands,
+            &defs,
+            &mut inventory,
+            index,
+            player_transform.translation().truncate(),
+        ),
+        None => {}
+    }
+}